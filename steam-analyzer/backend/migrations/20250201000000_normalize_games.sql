@@ -0,0 +1,35 @@
+-- Normalized copies of the Steam payloads kept in snapshots.json_data, so
+-- analytics can be done in SQL instead of parsing JSON blobs.
+
+-- One row per Steam app we have seen in any library
+CREATE TABLE IF NOT EXISTS games (
+    appid INTEGER PRIMARY KEY,
+    name TEXT,
+    img_icon_url TEXT, -- icon hash, see /api/images/icon/:appid/:hash
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Current library of each user, replaced on every owned_games refresh
+CREATE TABLE IF NOT EXISTS user_games (
+    steam_id TEXT NOT NULL,
+    appid INTEGER NOT NULL,
+    playtime_forever INTEGER NOT NULL DEFAULT 0, -- minutes
+    playtime_2weeks INTEGER NOT NULL DEFAULT 0, -- minutes
+    rtime_last_played INTEGER, -- unix seconds
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (steam_id, appid),
+    FOREIGN KEY(appid) REFERENCES games(appid)
+);
+
+CREATE INDEX idx_user_games_appid ON user_games(appid);
+
+-- Achievement state per user and game, replaced on every achievements refresh
+CREATE TABLE IF NOT EXISTS user_achievements (
+    steam_id TEXT NOT NULL,
+    appid INTEGER NOT NULL,
+    apiname TEXT NOT NULL,
+    achieved INTEGER NOT NULL DEFAULT 0, -- 0/1
+    unlocktime INTEGER, -- unix seconds, NULL while locked
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (steam_id, appid, apiname)
+);
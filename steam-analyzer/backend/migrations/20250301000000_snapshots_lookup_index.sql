@@ -0,0 +1,2 @@
+-- Latest-snapshot lookups, history ranges and retention all filter on these
+CREATE INDEX IF NOT EXISTS idx_snapshots_lookup ON snapshots(steam_id, data_type, created_at);
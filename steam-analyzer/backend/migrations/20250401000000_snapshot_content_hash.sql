@@ -0,0 +1,6 @@
+-- Identical refreshes bump last_seen_at on the latest row instead of
+-- inserting a duplicate. content_hash is a hex SHA-256 of json_data.
+ALTER TABLE snapshots ADD COLUMN content_hash TEXT;
+ALTER TABLE snapshots ADD COLUMN last_seen_at DATETIME;
+
+UPDATE snapshots SET last_seen_at = created_at;
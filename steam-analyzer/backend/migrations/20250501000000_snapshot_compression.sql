@@ -0,0 +1,4 @@
+-- json_data now holds zstd-compressed bytes (BLOB) for codec 'zstd'. Existing
+-- rows stay plain JSON text ('json') until the backend recompresses them on
+-- startup, see snapshots::compress_legacy_rows.
+ALTER TABLE snapshots ADD COLUMN codec TEXT NOT NULL DEFAULT 'json';
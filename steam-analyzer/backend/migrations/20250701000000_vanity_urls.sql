@@ -0,0 +1,6 @@
+-- Custom URL name -> SteamID64, so repeat lookups skip ResolveVanityURL
+CREATE TABLE IF NOT EXISTS vanity_urls (
+    vanity TEXT PRIMARY KEY,
+    steam_id TEXT NOT NULL,
+    resolved_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,43 @@
+//! Serves canned Steam, CDN and Gemini responses for local development.
+//!
+//! Point the backend at it with `STEAM_API_BASE_URL`, `STEAM_CDN_BASE_URL`,
+//! `STEAM_MEDIA_BASE_URL` and `GEMINI_API_BASE_URL`.
+
+use backend::mock_upstream::{self, MockOptions};
+use std::env;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() {
+    let addr: SocketAddr = env::var("MOCK_UPSTREAM_ADDR")
+        .unwrap_or_else(|_| "127.0.0.1:4000".to_string())
+        .parse()
+        .expect("Invalid MOCK_UPSTREAM_ADDR");
+
+    let options = MockOptions {
+        latency: Duration::from_millis(env_number("MOCK_LATENCY_MS").unwrap_or(0)),
+        rate_limit_every: env_number("MOCK_RATE_LIMIT_EVERY"),
+        fail_status: env_number("MOCK_FAIL_STATUS"),
+        fail_first: env_number("MOCK_FAIL_FIRST"),
+    };
+    println!("mock upstream options: {:?}", options);
+
+    let (addr, _mock) = mock_upstream::spawn(addr, options)
+        .await
+        .expect("Failed to bind mock upstream");
+    println!("mock upstream listening on http://{}", addr);
+
+    tokio::signal::ctrl_c().await.ok();
+}
+
+fn env_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            eprintln!("Ignoring invalid {}={:?}", name, value);
+            None
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::config::BreakerPolicy;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stops calling an upstream that keeps failing. After
+/// `failure_threshold` consecutive failures the circuit opens and calls fail
+/// fast for `open_for`; then a single probe is let through (half-open) and
+/// its outcome closes or re-opens the circuit.
+pub struct CircuitBreaker {
+    policy: BreakerPolicy,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed {
+        consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+        opened_at: DateTime<Utc>,
+    },
+    HalfOpen {
+        probe_in_flight: bool,
+        opened_at: DateTime<Utc>,
+    },
+}
+
+/// Snapshot of the breaker for the health endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerStatus {
+    /// `closed`, `open` or `half_open`.
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<DateTime<Utc>>,
+    /// Seconds until the next probe is allowed while open.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_seconds: Option<u64>,
+}
+
+/// Permission to make one call. Report how it went with
+/// [`Permit::success`] or [`Permit::failure`]; dropping it unreported (e.g.
+/// the request was cancelled) frees the probe slot without a verdict.
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    reported: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: BreakerPolicy) -> Self {
+        CircuitBreaker {
+            policy,
+            state: Mutex::new(BreakerState::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// A permit to call the upstream, or how long until the circuit allows
+    /// another attempt.
+    pub fn acquire(&self) -> Result<Permit<'_>, Duration> {
+        let mut state = self.state.lock().unwrap();
+        let probe = match *state {
+            BreakerState::Closed { .. } => false,
+            BreakerState::Open { until, opened_at } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(until - now);
+                }
+                *state = BreakerState::HalfOpen {
+                    probe_in_flight: true,
+                    opened_at,
+                };
+                true
+            }
+            BreakerState::HalfOpen {
+                probe_in_flight: false,
+                opened_at,
+            } => {
+                *state = BreakerState::HalfOpen {
+                    probe_in_flight: true,
+                    opened_at,
+                };
+                true
+            }
+            // Someone else is probing; check back shortly.
+            BreakerState::HalfOpen { .. } => return Err(Duration::from_secs(1)),
+        };
+        Ok(Permit {
+            breaker: self,
+            probe,
+            reported: false,
+        })
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        match *self.state.lock().unwrap() {
+            BreakerState::Closed {
+                consecutive_failures,
+            } => BreakerStatus {
+                state: "closed",
+                consecutive_failures,
+                opened_at: None,
+                retry_in_seconds: None,
+            },
+            BreakerState::Open { until, opened_at } => BreakerStatus {
+                state: "open",
+                consecutive_failures: self.policy.failure_threshold,
+                opened_at: Some(opened_at),
+                retry_in_seconds: Some(
+                    until
+                        .saturating_duration_since(Instant::now())
+                        .as_secs_f64()
+                        .ceil() as u64,
+                ),
+            },
+            BreakerState::HalfOpen { opened_at, .. } => BreakerStatus {
+                state: "half_open",
+                consecutive_failures: self.policy.failure_threshold,
+                opened_at: Some(opened_at),
+                retry_in_seconds: None,
+            },
+        }
+    }
+
+    /// Open and not yet due for a probe: any call would be refused.
+    pub fn is_open(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            BreakerState::Open { until, .. } => Instant::now() < until,
+            _ => false,
+        }
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, BreakerState::Closed { .. }) {
+            eprintln!("✅ Steam circuit closed, probe succeeded");
+        }
+        *state = BreakerState::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    fn on_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let trip = |opened_at| BreakerState::Open {
+            until: Instant::now() + self.policy.open_for,
+            opened_at,
+        };
+        *state = match *state {
+            BreakerState::Closed {
+                consecutive_failures,
+            } if consecutive_failures + 1 >= self.policy.failure_threshold => {
+                eprintln!(
+                    "⚡ Steam circuit opened after {} consecutive failures",
+                    consecutive_failures + 1
+                );
+                trip(Utc::now())
+            }
+            BreakerState::Closed {
+                consecutive_failures,
+            } => BreakerState::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            // The probe failed: back to open for another full period.
+            BreakerState::HalfOpen { opened_at, .. } => trip(opened_at),
+            open @ BreakerState::Open { .. } => open,
+        };
+    }
+
+    fn on_abandoned_probe(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let BreakerState::HalfOpen { opened_at, .. } = *state {
+            *state = BreakerState::HalfOpen {
+                probe_in_flight: false,
+                opened_at,
+            };
+        }
+    }
+}
+
+impl Permit<'_> {
+    pub fn success(mut self) {
+        self.reported = true;
+        self.breaker.on_success();
+    }
+
+    pub fn failure(mut self) {
+        self.reported = true;
+        self.breaker.on_failure();
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.reported {
+            self.breaker.on_abandoned_probe();
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::steam_api::OwnedGames;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+pub struct CommonGame {
+    pub appid: u32,
+    pub name: Option<String>,
+    pub img_icon_url: Option<String>,
+    /// Minutes, summed over every player.
+    pub combined_minutes: u64,
+    /// Minutes per player, in the order the players were given.
+    pub players: Vec<PlayerPlaytime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerPlaytime {
+    pub steam_id: String,
+    pub minutes: u64,
+}
+
+/// Games present in every library, most played (all players combined) first.
+pub fn common_games(libraries: &[(&str, &OwnedGames)]) -> Vec<CommonGame> {
+    let Some(((_, first), rest)) = libraries.split_first() else {
+        return Vec::new();
+    };
+    let others: Vec<HashMap<u32, u64>> = rest
+        .iter()
+        .map(|(_, library)| {
+            library
+                .games
+                .iter()
+                .flatten()
+                .map(|g| (g.appid, g.playtime_forever as u64))
+                .collect()
+        })
+        .collect();
+
+    let mut common: Vec<CommonGame> = first
+        .games
+        .iter()
+        .flatten()
+        .filter_map(|game| {
+            let mut minutes = vec![game.playtime_forever as u64];
+            for other in &others {
+                minutes.push(*other.get(&game.appid)?);
+            }
+            Some(CommonGame {
+                appid: game.appid,
+                name: game.name.clone(),
+                img_icon_url: game.img_icon_url.clone(),
+                combined_minutes: minutes.iter().sum(),
+                players: libraries
+                    .iter()
+                    .zip(minutes)
+                    .map(|((steam_id, _), minutes)| PlayerPlaytime {
+                        steam_id: steam_id.to_string(),
+                        minutes,
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    common.sort_by_key(|g| (Reverse(g.combined_minutes), g.appid));
+    common
+}
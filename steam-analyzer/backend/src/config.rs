@@ -0,0 +1,230 @@
+use std::env;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Runtime settings read from the environment once at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub cache_ttl: CacheTtl,
+    pub retention: RetentionPolicy,
+    pub upstreams: Upstreams,
+    pub timeouts: Timeouts,
+    pub steam_fixtures: FixtureMode,
+    pub steam_retry: RetryPolicy,
+    pub steam_breaker: BreakerPolicy,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Config {
+            cache_ttl: CacheTtl::from_env(),
+            retention: RetentionPolicy::from_env(),
+            upstreams: Upstreams::from_env(),
+            timeouts: Timeouts::from_env(),
+            steam_fixtures: FixtureMode::from_env(),
+            steam_retry: RetryPolicy::from_env(),
+            steam_breaker: BreakerPolicy::from_env(),
+        }
+    }
+}
+
+/// How long a cached snapshot of each Steam data type counts as fresh.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheTtl {
+    pub player_summary: Duration,
+    pub owned_games: Duration,
+    pub achievements: Duration,
+    pub recent_games: Duration,
+    pub friend_list: Duration,
+    /// Custom URL name to SteamID64 mappings.
+    pub vanity_url: Duration,
+}
+
+impl CacheTtl {
+    fn from_env() -> Self {
+        CacheTtl {
+            player_summary: env_secs("CACHE_TTL_SUMMARY_SECS", 60 * 60),
+            owned_games: env_secs("CACHE_TTL_OWNED_GAMES_SECS", 6 * 60 * 60),
+            achievements: env_secs("CACHE_TTL_ACHIEVEMENTS_SECS", 24 * 60 * 60),
+            recent_games: env_secs("CACHE_TTL_RECENT_GAMES_SECS", 60 * 60),
+            friend_list: env_secs("CACHE_TTL_FRIENDS_SECS", 6 * 60 * 60),
+            vanity_url: env_secs("CACHE_TTL_VANITY_URL_SECS", 24 * 60 * 60),
+        }
+    }
+}
+
+/// How snapshot history is thinned out as it ages: every row is kept for
+/// `keep_all_days`, then one row per day until `keep_daily_days`, then one row
+/// per week. The latest row of each profile/data type always survives.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub keep_all_days: u32,
+    pub keep_daily_days: u32,
+    /// How often the background job runs.
+    pub interval: Duration,
+}
+
+impl RetentionPolicy {
+    fn from_env() -> Self {
+        let keep_all_days = env_parse("RETENTION_KEEP_ALL_DAYS", 7);
+        RetentionPolicy {
+            keep_all_days,
+            keep_daily_days: env_parse("RETENTION_KEEP_DAILY_DAYS", 90).max(keep_all_days),
+            interval: env_secs("RETENTION_INTERVAL_SECS", 6 * 60 * 60),
+        }
+    }
+}
+
+/// Base URLs of everything we call out to, overridable so the backend can be
+/// pointed at a local stub server. Stored without a trailing slash.
+#[derive(Debug, Clone)]
+pub struct Upstreams {
+    /// Steam Web API (`ISteamUser`, `IPlayerService`, ...).
+    pub steam_api: String,
+    /// Store assets CDN serving banners (`/store_item_assets/...`).
+    pub steam_cdn: String,
+    /// Community media host serving game icons (`/steamcommunity/...`).
+    pub steam_media: String,
+    /// Google Generative Language API.
+    pub gemini: String,
+}
+
+impl Upstreams {
+    fn from_env() -> Self {
+        Upstreams {
+            steam_api: env_url("STEAM_API_BASE_URL", "http://api.steampowered.com"),
+            steam_cdn: env_url(
+                "STEAM_CDN_BASE_URL",
+                "https://shared.akamai.steamstatic.com",
+            ),
+            steam_media: env_url("STEAM_MEDIA_BASE_URL", "https://media.steampowered.com"),
+            gemini: env_url(
+                "GEMINI_API_BASE_URL",
+                "https://generativelanguage.googleapis.com",
+            ),
+        }
+    }
+}
+
+/// Per-upstream limits on how long one request may take, start to finish.
+/// `connect` applies to every upstream.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub steam_api: Duration,
+    pub cdn: Duration,
+    pub gemini: Duration,
+}
+
+impl Timeouts {
+    fn from_env() -> Self {
+        Timeouts {
+            connect: env_secs("HTTP_CONNECT_TIMEOUT_SECS", 5),
+            steam_api: env_secs("STEAM_API_TIMEOUT_SECS", 10),
+            cdn: env_secs("CDN_TIMEOUT_SECS", 10),
+            // Generation is slow; give it room
+            gemini: env_secs("GEMINI_TIMEOUT_SECS", 60),
+        }
+    }
+}
+
+/// How Steam requests are retried on 429s, 5xx and network errors. Delays
+/// double from `base_delay` (with jitter) up to `max_delay`; a `Retry-After`
+/// from Steam replaces the computed delay, and one longer than `max_delay`
+/// ends the retries.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn from_env() -> Self {
+        RetryPolicy {
+            max_retries: env_parse("STEAM_RETRY_MAX_RETRIES", 3),
+            base_delay: Duration::from_millis(env_parse("STEAM_RETRY_BASE_DELAY_MS", 1000)),
+            max_delay: env_secs("STEAM_RETRY_MAX_DELAY_SECS", 30),
+        }
+    }
+}
+
+/// When to stop calling Steam: after `failure_threshold` consecutive failed
+/// calls (each already retried per [`RetryPolicy`]) the circuit opens for
+/// `open_for`, then a single probe decides whether to close it again.
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerPolicy {
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+}
+
+impl BreakerPolicy {
+    fn from_env() -> Self {
+        BreakerPolicy {
+            failure_threshold: env_parse("STEAM_BREAKER_FAILURES", 5).max(1),
+            open_for: env_secs("STEAM_BREAKER_OPEN_SECS", 30),
+        }
+    }
+}
+
+/// Record/replay of Steam Web API responses, for reproducing bug reports and
+/// deterministic tests. Fixtures are JSON files in the given directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Talk to Steam normally.
+    #[default]
+    Off,
+    /// Talk to Steam and save every response (minus the API key).
+    Record(PathBuf),
+    /// Never talk to Steam; a request without a fixture is an error.
+    Replay(PathBuf),
+}
+
+impl FixtureMode {
+    fn from_env() -> Self {
+        let dir = env::var("STEAM_FIXTURES_DIR")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("fixtures/steam"));
+        match env::var("STEAM_FIXTURES_MODE").as_deref().map(str::trim) {
+            Ok("record") => FixtureMode::Record(dir),
+            Ok("replay") => FixtureMode::Replay(dir),
+            Ok("") | Ok("off") | Err(_) => FixtureMode::Off,
+            Ok(other) => {
+                eprintln!(
+                    "Ignoring invalid STEAM_FIXTURES_MODE={:?}, using off",
+                    other
+                );
+                FixtureMode::Off
+            }
+        }
+    }
+}
+
+fn env_url(name: &str, default: &str) -> String {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.to_string())
+        .trim()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn env_secs(name: &str, default: u64) -> Duration {
+    Duration::from_secs(env_parse(name, default))
+}
+
+fn env_parse<T: FromStr + Display + Copy>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Ignoring invalid {}={:?}, using {}", name, value, default);
+            default
+        }),
+        Err(_) => default,
+    }
+}
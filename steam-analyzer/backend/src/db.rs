@@ -1,31 +1,89 @@
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::Config;
+use crate::single_flight::SingleFlight;
+use crate::snapshots;
+use crate::steam_api::{HttpSteamClient, SteamClient};
 use governor::{
-    clock::DefaultClock,
-    middleware::NoOpMiddleware,
-    state::{keyed::DefaultKeyedStateStore, InMemoryState},
-    Quota, RateLimiter,
+    clock::DefaultClock, middleware::NoOpMiddleware, state::keyed::DefaultKeyedStateStore, Quota,
+    RateLimiter,
 };
-use nonzero_ext::nonzero;
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
 use std::env;
 use std::net::IpAddr;
+use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Sent to Steam and Gemini so they can tell who is calling.
+pub const USER_AGENT: &str = concat!("steam-analyzer/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Pool<Sqlite>,
+    pub config: Arc<Config>,
     pub client: reqwest::Client,
-    pub steam_global_limiter:
-        Arc<RateLimiter<governor::state::NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>>,
+    /// Steam Web API access; every call waits on the global Steam limiter.
+    pub steam: Arc<dyn SteamClient>,
+    /// Shared with the HTTP Steam client; read by the health endpoint.
+    pub steam_breaker: Arc<CircuitBreaker>,
     pub user_limiter:
         Arc<RateLimiter<IpAddr, DefaultKeyedStateStore<IpAddr>, DefaultClock, NoOpMiddleware>>,
+    /// Coalesces concurrent Steam fetches keyed by `(steam_id, data_type)`.
+    pub in_flight: Arc<SingleFlight<(String, String)>>,
+}
+
+impl AppState {
+    pub fn new(db: Pool<Sqlite>, config: Arc<Config>) -> Self {
+        // Shared by every upstream; each request sets its own total timeout.
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(config.timeouts.connect)
+            .build()
+            .expect("Failed to build HTTP client");
+
+        // Global Steam Rate Limiter: ~200 requests per 5 minutes
+        // We'll set it to 190 to be safe
+        let steam_quota = Quota::with_period(Duration::from_secs(300))
+            .unwrap()
+            .allow_burst(NonZeroU32::new(190).unwrap());
+        let steam_global_limiter = Arc::new(RateLimiter::direct(steam_quota));
+        let steam_breaker = Arc::new(CircuitBreaker::new(config.steam_breaker));
+        let steam = Arc::new(HttpSteamClient::new(
+            client.clone(),
+            &config,
+            env::var("STEAM_API_KEY").unwrap_or_default(),
+            steam_global_limiter,
+            steam_breaker.clone(),
+        ));
+
+        // User Rate Limiter: 30 requests per minute
+        let user_quota = Quota::with_period(Duration::from_secs(60))
+            .unwrap()
+            .allow_burst(NonZeroU32::new(30).unwrap());
+        let user_limiter = Arc::new(RateLimiter::keyed(user_quota));
+
+        AppState {
+            db,
+            config,
+            client,
+            steam,
+            steam_breaker,
+            user_limiter,
+            in_flight: Default::default(),
+        }
+    }
 }
 
 pub async fn init_db() -> Result<Pool<Sqlite>, sqlx::Error> {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    connect(&database_url).await
+}
 
+/// Opens the pool, runs migrations and upgrades legacy rows.
+pub async fn connect(database_url: &str) -> Result<Pool<Sqlite>, sqlx::Error> {
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect(database_url)
         .await?;
 
     // Run migrations
@@ -33,5 +91,10 @@ pub async fn init_db() -> Result<Pool<Sqlite>, sqlx::Error> {
 
     println!("✅ Database migration success");
 
+    let compressed = snapshots::compress_legacy_rows(&pool).await?;
+    if compressed > 0 {
+        println!("✅ Compressed {} legacy snapshot rows", compressed);
+    }
+
     Ok(pool)
 }
@@ -0,0 +1,209 @@
+use crate::redact;
+use crate::steam_api::SteamApiError;
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::fmt;
+use std::time::Duration;
+
+/// Errors returned by API handlers. Each variant maps to an HTTP status and a
+/// stable `code` clients can match on; the message is for humans.
+///
+/// Serialized as `{"error": {"code": "...", "message": "..."}}`.
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    /// 400: the id/URL could not be parsed as any Steam identifier.
+    InvalidSteamId(String),
+    /// 400: some other query/body parameter is malformed.
+    BadRequest(String),
+    /// 403: the profile or its game details are private, so Steam won't
+    /// share them with us.
+    ProfilePrivate(String),
+    /// 404: the id is well-formed but no Steam profile matches it.
+    ProfileNotFound(String),
+    /// 404: Steam has no stats for this game (e.g. it has no achievements).
+    NoStats(String),
+    /// 429: the caller exceeded one of our own limits.
+    RateLimited {
+        message: String,
+        retry_after: Duration,
+    },
+    /// 502: Steam answered with something we could not use.
+    SteamBadResponse(String),
+    /// 503: Steam is unreachable, erroring or throttling us.
+    SteamUnavailable {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// 502: the AI provider rejected or failed the request.
+    AiProviderError(String),
+    /// 503: the server is missing configuration (e.g. an API key).
+    NotConfigured(String),
+    /// 503: replay mode has no recording of a Steam request. Never answered
+    /// from the cache, so a missing fixture can't go unnoticed.
+    FixtureMissing(String),
+    /// 504: an upstream (Steam, the CDN, the AI provider) did not answer in
+    /// time.
+    UpstreamTimeout(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidSteamId(_) | ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::ProfilePrivate(_) => StatusCode::FORBIDDEN,
+            ApiError::ProfileNotFound(_) | ApiError::NoStats(_) => StatusCode::NOT_FOUND,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::SteamBadResponse(_) | ApiError::AiProviderError(_) => StatusCode::BAD_GATEWAY,
+            ApiError::SteamUnavailable { .. }
+            | ApiError::NotConfigured(_)
+            | ApiError::FixtureMissing(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidSteamId(_) => "invalid_steam_id",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::ProfilePrivate(_) => "profile_private",
+            ApiError::ProfileNotFound(_) => "profile_not_found",
+            ApiError::NoStats(_) => "no_stats",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::SteamBadResponse(_) => "steam_bad_response",
+            ApiError::SteamUnavailable { .. } => "steam_unavailable",
+            ApiError::AiProviderError(_) => "ai_provider_error",
+            ApiError::NotConfigured(_) => "not_configured",
+            ApiError::FixtureMissing(_) => "fixture_missing",
+            ApiError::UpstreamTimeout(_) => "upstream_timeout",
+        }
+    }
+
+    /// The upstream is down, hung or refused by the circuit breaker, so a
+    /// cached copy is a better answer than this error.
+    pub fn is_upstream_outage(&self) -> bool {
+        matches!(
+            self,
+            ApiError::SteamUnavailable { .. } | ApiError::UpstreamTimeout(_)
+        )
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::InvalidSteamId(m)
+            | ApiError::BadRequest(m)
+            | ApiError::ProfilePrivate(m)
+            | ApiError::ProfileNotFound(m)
+            | ApiError::NoStats(m)
+            | ApiError::SteamBadResponse(m)
+            | ApiError::AiProviderError(m)
+            | ApiError::NotConfigured(m)
+            | ApiError::FixtureMissing(m)
+            | ApiError::UpstreamTimeout(m) => m,
+            ApiError::RateLimited { message, .. } | ApiError::SteamUnavailable { message, .. } => {
+                message
+            }
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::RateLimited { retry_after, .. } => Some(*retry_after),
+            ApiError::SteamUnavailable { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn too_many_requests(retry_after: Duration) -> Self {
+        ApiError::RateLimited {
+            message: "Too many requests. Please try again later.".to_string(),
+            retry_after,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": {
+                "code": self.code(),
+                "message": redact::redact(self.message()),
+            }
+        }));
+        let mut response = (self.status(), body).into_response();
+
+        if let Some(retry_after) = self.retry_after() {
+            // Retry-After only speaks whole seconds; never tell clients 0.
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+
+        response
+    }
+}
+
+impl From<SteamApiError> for ApiError {
+    fn from(e: SteamApiError) -> Self {
+        eprintln!("Steam API error: {}", redact::redact(&e.to_string()));
+        match e {
+            SteamApiError::PrivateProfile => ApiError::ProfilePrivate(
+                "This Steam profile's game details are private. In Steam, open Edit Profile > \
+                 Privacy Settings and set both \"My profile\" and \"Game details\" to Public, \
+                 then try again."
+                    .to_string(),
+            ),
+            SteamApiError::NoStats(message) => ApiError::NoStats(message),
+            SteamApiError::Status {
+                status,
+                retry_after,
+            } if status == reqwest::StatusCode::TOO_MANY_REQUESTS => ApiError::SteamUnavailable {
+                message: "Steam is rate limiting us. Please try again shortly.".to_string(),
+                // Steam's own estimate beats our guess
+                retry_after: retry_after.or(Some(Duration::from_secs(60))),
+            },
+            SteamApiError::Status {
+                status,
+                retry_after,
+            } if status.is_server_error() => ApiError::SteamUnavailable {
+                message: format!("Steam is currently unavailable ({})", status),
+                retry_after,
+            },
+            SteamApiError::Http(e) if e.is_timeout() => {
+                ApiError::UpstreamTimeout("Steam did not respond in time".to_string())
+            }
+            SteamApiError::Http(_) => ApiError::SteamUnavailable {
+                message: "Could not reach Steam".to_string(),
+                retry_after: None,
+            },
+            SteamApiError::Status { status, .. } => {
+                ApiError::SteamBadResponse(format!("Steam rejected the request ({})", status))
+            }
+            SteamApiError::Decode(_) => {
+                ApiError::SteamBadResponse("Steam returned an unexpected response".to_string())
+            }
+            // Only happens in replay mode, where the message is what a
+            // developer needs to see.
+            SteamApiError::Fixture(message) => ApiError::FixtureMissing(message),
+            SteamApiError::CircuitOpen(retry_in) => ApiError::SteamUnavailable {
+                message: "Steam is unavailable right now. Please try again shortly.".to_string(),
+                retry_after: Some(retry_in),
+            },
+            SteamApiError::MissingApiKey => ApiError::NotConfigured(
+                "Server configuration error: Missing Steam API Key".to_string(),
+            ),
+        }
+    }
+}
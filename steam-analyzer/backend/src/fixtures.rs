@@ -0,0 +1,93 @@
+use crate::redact;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One recorded Steam response. The request line is kept for humans reading
+/// the file; lookups go by file name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fixture {
+    /// Path and query of the request, without credentials.
+    pub request: String,
+    pub status: u16,
+    /// The body when it was JSON (the usual case)...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// ...otherwise the raw text, e.g. Steam's HTML error pages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl Fixture {
+    pub fn body_bytes(&self) -> Vec<u8> {
+        match (&self.body, &self.text) {
+            (Some(body), _) => serde_json::to_vec(body).unwrap_or_default(),
+            (None, Some(text)) => text.clone().into_bytes(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+/// The request as it is recorded: path plus the query with credential
+/// parameters dropped and the rest sorted, so the key in use and parameter
+/// order don't matter.
+pub fn request_line(url: &Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !redact::is_secret_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    pairs.sort();
+    let query: Vec<String> = pairs
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    if query.is_empty() {
+        url.path().to_string()
+    } else {
+        format!("{}?{}", url.path(), query.join("&"))
+    }
+}
+
+/// `ISteamUser.GetPlayerSummaries.v0002-<hash of the request line>.json`
+pub fn path_for(dir: &Path, url: &Url) -> PathBuf {
+    let endpoint: Vec<&str> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(request_line(url).as_bytes()));
+    dir.join(format!("{}-{}.json", endpoint.join("."), &hash[..16]))
+}
+
+pub async fn load(dir: &Path, url: &Url) -> io::Result<Fixture> {
+    let raw = tokio::fs::read(path_for(dir, url)).await?;
+    serde_json::from_slice(&raw).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub async fn save(dir: &Path, url: &Url, status: u16, body: &[u8]) -> io::Result<()> {
+    // Steam doesn't echo the key, but never write one to disk if it did
+    let text = redact::redact(&String::from_utf8_lossy(body));
+    let fixture = match serde_json::from_str::<Value>(&text) {
+        Ok(body) => Fixture {
+            request: request_line(url),
+            status,
+            body: Some(body),
+            text: None,
+        },
+        Err(_) => Fixture {
+            request: request_line(url),
+            status,
+            body: None,
+            text: Some(text),
+        },
+    };
+
+    tokio::fs::create_dir_all(dir).await?;
+    let json = serde_json::to_vec_pretty(&fixture)?;
+    tokio::fs::write(path_for(dir, url), json).await
+}
@@ -0,0 +1,412 @@
+use crate::snapshots::Snapshot;
+use crate::steam_api::{OwnedGames, SteamResponse};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Daily,
+    Weekly,
+}
+
+impl Granularity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "daily" | "day" => Some(Granularity::Daily),
+            "weekly" | "week" => Some(Granularity::Weekly),
+            _ => None,
+        }
+    }
+
+    /// Weekly buckets start on Monday (UTC), or on the earliest date
+    /// representable when that Monday is not.
+    pub fn bucket_start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Daily => date,
+            Granularity::Weekly => date
+                .checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+                .unwrap_or(NaiveDate::MIN),
+        }
+    }
+
+    /// Longest `from..=to` range, in days, one request may cover: a year of
+    /// daily buckets or about three years of weekly ones.
+    pub fn max_range_days(self) -> u64 {
+        match self {
+            Granularity::Daily => 366,
+            Granularity::Weekly => 3 * 366,
+        }
+    }
+
+    fn length(self) -> Days {
+        match self {
+            Granularity::Daily => Days::new(1),
+            Granularity::Weekly => Days::new(7),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaytimeHistory {
+    pub granularity: Granularity,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_minutes: u64,
+    pub buckets: Vec<PlaytimeBucket>,
+    /// Minutes per game over the whole range, most played first.
+    pub games: Vec<GameTotal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaytimeBucket {
+    /// First day of the bucket (inclusive).
+    pub start: NaiveDate,
+    /// First day after the bucket (exclusive).
+    pub end: NaiveDate,
+    /// Snapshots taken during the bucket. With none, playtime cannot be
+    /// attributed and rolls into the next bucket that has one.
+    pub snapshots: usize,
+    pub total_minutes: u64,
+    /// Games played in this bucket or the previous one, biggest first.
+    pub games: Vec<GameDelta>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameDelta {
+    pub appid: u32,
+    pub name: Option<String>,
+    pub minutes: u64,
+    /// Compared with the previous bucket: positive rose, negative fell.
+    pub change_minutes: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameTotal {
+    pub appid: u32,
+    pub name: Option<String>,
+    pub minutes: u64,
+}
+
+type Playtimes = HashMap<u32, u64>;
+
+fn playtimes(snapshot: &Snapshot<SteamResponse<OwnedGames>>) -> Playtimes {
+    snapshot
+        .data
+        .response
+        .games
+        .iter()
+        .flatten()
+        .map(|g| (g.appid, g.playtime_forever as u64))
+        .collect()
+}
+
+/// Turns consecutive `owned_games` snapshots (oldest first, optionally led by
+/// one taken before `from`) into playtime per bucket between `from` and `to`
+/// inclusive. Each bucket's playtime is the growth of `playtime_forever`
+/// between its last snapshot and the previous bucket's last snapshot.
+/// Snapshots of a hidden library (cached as `{}` by older versions) are
+/// skipped rather than read as every game at zero minutes.
+pub fn playtime_history(
+    snapshots: &[Snapshot<SteamResponse<OwnedGames>>],
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: Granularity,
+) -> PlaytimeHistory {
+    let snapshots: Vec<_> = snapshots
+        .iter()
+        .filter(|s| s.data.response.ensure_visible().is_ok())
+        .collect();
+    let names: HashMap<u32, String> = snapshots
+        .iter()
+        .flat_map(|s| s.data.response.games.iter().flatten())
+        .filter_map(|g| g.name.clone().map(|name| (g.appid, name)))
+        .collect();
+    let name_of = |appid: u32| names.get(&appid).cloned();
+
+    let first_start = granularity.bucket_start(from);
+    let mut remaining = snapshots.into_iter().peekable();
+
+    // Playtimes at the end of the last bucket that had data.
+    let mut reference: Option<Playtimes> = None;
+    while let Some(snapshot) = remaining.next_if(|s| s.fetched_at.date_naive() < first_start) {
+        reference = Some(playtimes(snapshot));
+    }
+
+    let mut buckets = Vec::new();
+    let mut totals: BTreeMap<u32, u64> = BTreeMap::new();
+    let mut previous: Playtimes = HashMap::new();
+    let mut start = first_start;
+
+    while start <= to {
+        let Some(end) = start.checked_add_days(granularity.length()) else {
+            break;
+        };
+        let in_bucket: Vec<_> =
+            std::iter::from_fn(|| remaining.next_if(|s| s.fetched_at.date_naive() < end)).collect();
+
+        let mut minutes: Playtimes = HashMap::new();
+        if let (Some(opening), Some(closing)) = (in_bucket.first(), in_bucket.last()) {
+            let closing = playtimes(closing);
+            let base = reference.take().unwrap_or_else(|| playtimes(opening));
+            for (&appid, &played) in &closing {
+                let delta = played.saturating_sub(base.get(&appid).copied().unwrap_or(0));
+                if delta > 0 {
+                    minutes.insert(appid, delta);
+                }
+            }
+            reference = Some(closing);
+        }
+
+        let mut games: Vec<GameDelta> = minutes
+            .keys()
+            .chain(previous.keys())
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|appid| {
+                let now = minutes.get(&appid).copied().unwrap_or(0);
+                let before = previous.get(&appid).copied().unwrap_or(0);
+                GameDelta {
+                    appid,
+                    name: name_of(appid),
+                    minutes: now,
+                    change_minutes: now as i64 - before as i64,
+                }
+            })
+            .collect();
+        games.sort_by(|a, b| {
+            b.minutes
+                .cmp(&a.minutes)
+                .then(a.change_minutes.cmp(&b.change_minutes))
+        });
+
+        for (&appid, &played) in &minutes {
+            *totals.entry(appid).or_default() += played;
+        }
+
+        buckets.push(PlaytimeBucket {
+            start,
+            end,
+            snapshots: in_bucket.len(),
+            total_minutes: minutes.values().sum(),
+            games,
+        });
+        previous = minutes;
+        start = end;
+    }
+
+    let mut games: Vec<GameTotal> = totals
+        .into_iter()
+        .map(|(appid, minutes)| GameTotal {
+            appid,
+            name: name_of(appid),
+            minutes,
+        })
+        .collect();
+    games.sort_by_key(|g| Reverse(g.minutes));
+
+    PlaytimeHistory {
+        granularity,
+        from,
+        to,
+        total_minutes: games.iter().map(|g| g.minutes).sum(),
+        buckets,
+        games,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::steam_api::OwnedGame;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    /// An `owned_games` snapshot taken at noon UTC on `day`.
+    fn snapshot(day: &str, playtimes: &[(u32, u32)]) -> Snapshot<SteamResponse<OwnedGames>> {
+        let games = playtimes
+            .iter()
+            .map(|&(appid, playtime_forever)| OwnedGame {
+                appid,
+                name: Some(format!("Game {}", appid)),
+                playtime_forever,
+                playtime_2weeks: None,
+                img_icon_url: None,
+                has_community_visible_stats: None,
+                rtime_last_played: None,
+            })
+            .collect::<Vec<_>>();
+        Snapshot {
+            data: SteamResponse {
+                response: OwnedGames {
+                    game_count: Some(games.len() as u32),
+                    games: Some(games),
+                },
+            },
+            fetched_at: date(day).and_hms_opt(12, 0, 0).unwrap().and_utc(),
+            from_cache: true,
+            stale: false,
+        }
+    }
+
+    fn minutes(history: &PlaytimeHistory) -> Vec<u64> {
+        history.buckets.iter().map(|b| b.total_minutes).collect()
+    }
+
+    #[test]
+    fn snapshot_before_from_is_the_baseline() {
+        let snapshots = [
+            snapshot("2024-03-01", &[(10, 100)]),
+            snapshot("2024-03-05", &[(10, 160)]),
+            snapshot("2024-03-06", &[(10, 170)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-05"),
+            date("2024-03-06"),
+            Granularity::Daily,
+        );
+
+        // Without the baseline the first day would have nothing to diff against
+        assert_eq!(minutes(&history), [60, 10]);
+        assert_eq!(history.total_minutes, 70);
+    }
+
+    #[test]
+    fn first_bucket_without_baseline_diffs_within_itself() {
+        let snapshots = [
+            snapshot("2024-03-05", &[(10, 100)]),
+            snapshot("2024-03-05", &[(10, 130)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-05"),
+            date("2024-03-05"),
+            Granularity::Daily,
+        );
+        assert_eq!(minutes(&history), [30]);
+    }
+
+    #[test]
+    fn weekly_buckets_start_on_the_monday_before_from() {
+        // 2024-01-03 is a Wednesday
+        let snapshots = [
+            snapshot("2024-01-01", &[(10, 100)]),
+            snapshot("2024-01-02", &[(10, 130)]),
+            snapshot("2024-01-09", &[(10, 200)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-01-03"),
+            date("2024-01-10"),
+            Granularity::Weekly,
+        );
+
+        assert_eq!(history.buckets.len(), 2);
+        assert_eq!(history.buckets[0].start, date("2024-01-01"));
+        assert_eq!(history.buckets[0].end, date("2024-01-08"));
+        assert_eq!(history.buckets[0].snapshots, 2);
+        assert_eq!(minutes(&history), [30, 70]);
+    }
+
+    #[test]
+    fn buckets_without_snapshots_roll_into_the_next() {
+        let snapshots = [
+            snapshot("2024-03-01", &[(10, 100)]),
+            snapshot("2024-03-04", &[(10, 190)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-02"),
+            date("2024-03-04"),
+            Granularity::Daily,
+        );
+        assert_eq!(minutes(&history), [0, 0, 90]);
+    }
+
+    #[test]
+    fn playtime_going_down_counts_as_zero() {
+        // Steam sometimes corrects playtime downwards, e.g. after a refund
+        let snapshots = [
+            snapshot("2024-03-01", &[(10, 500), (20, 50)]),
+            snapshot("2024-03-02", &[(10, 400), (20, 80)]),
+            snapshot("2024-03-03", &[(10, 450), (20, 80)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-02"),
+            date("2024-03-03"),
+            Granularity::Daily,
+        );
+
+        assert_eq!(minutes(&history), [30, 50]);
+        let games = &history.buckets[0].games;
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].appid, 20);
+        // Totals are the sum of the non-negative deltas
+        let totals: Vec<_> = history.games.iter().map(|g| (g.appid, g.minutes)).collect();
+        assert_eq!(totals, [(10, 50), (20, 30)]);
+    }
+
+    #[test]
+    fn hidden_library_snapshots_are_skipped() {
+        let mut hidden = snapshot("2024-03-02", &[]);
+        hidden.data.response = OwnedGames {
+            game_count: None,
+            games: None,
+        };
+        let snapshots = [
+            snapshot("2024-03-01", &[(10, 100)]),
+            hidden,
+            snapshot("2024-03-03", &[(10, 130)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-02"),
+            date("2024-03-03"),
+            Granularity::Daily,
+        );
+
+        // Not a drop to zero followed by all 130 minutes at once
+        assert_eq!(minutes(&history), [0, 30]);
+        assert_eq!(history.buckets[0].snapshots, 0);
+    }
+
+    #[test]
+    fn deltas_compare_with_the_previous_bucket() {
+        let snapshots = [
+            snapshot("2024-03-01", &[(10, 0), (20, 0)]),
+            snapshot("2024-03-02", &[(10, 100), (20, 10)]),
+            snapshot("2024-03-03", &[(10, 130), (20, 50)]),
+        ];
+        let history = playtime_history(
+            &snapshots,
+            date("2024-03-02"),
+            date("2024-03-03"),
+            Granularity::Daily,
+        );
+
+        let day2: Vec<_> = history.buckets[1]
+            .games
+            .iter()
+            .map(|g| (g.appid, g.minutes, g.change_minutes))
+            .collect();
+        // Most played first; game 10 fell from 100 to 30, game 20 rose from 10 to 40
+        assert_eq!(day2, [(20, 40, 30), (10, 30, -70)]);
+    }
+
+    #[test]
+    fn range_limits_depend_on_the_bucket() {
+        assert_eq!(Granularity::Daily.max_range_days(), 366);
+        assert!(Granularity::Weekly.max_range_days() > 366);
+        assert_eq!(
+            Granularity::Weekly.bucket_start(NaiveDate::MIN),
+            NaiveDate::MIN
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::steam_api::{OwnedGames, PlayerStats};
+use sqlx::{Pool, Sqlite};
+
+/// Replaces the user's library with `owned`. Payloads without a game list
+/// (private libraries) are skipped so we don't wipe what we already know.
+pub async fn owned_games(
+    db: &Pool<Sqlite>,
+    steam_id: &str,
+    owned: &OwnedGames,
+) -> Result<(), sqlx::Error> {
+    let Some(games) = &owned.games else {
+        return Ok(());
+    };
+
+    let mut tx = db.begin().await?;
+
+    for game in games {
+        sqlx::query(
+            "INSERT INTO games (appid, name, img_icon_url) VALUES (?, ?, ?)
+             ON CONFLICT(appid) DO UPDATE SET
+                name = COALESCE(excluded.name, games.name),
+                img_icon_url = COALESCE(excluded.img_icon_url, games.img_icon_url),
+                updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(game.appid)
+        .bind(&game.name)
+        .bind(&game.img_icon_url)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM user_games WHERE steam_id = ?")
+        .bind(steam_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for game in games {
+        sqlx::query(
+            "INSERT INTO user_games (steam_id, appid, playtime_forever, playtime_2weeks, rtime_last_played)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(steam_id)
+        .bind(game.appid)
+        .bind(game.playtime_forever)
+        .bind(game.playtime_2weeks.unwrap_or(0))
+        .bind(game.rtime_last_played.filter(|&t| t > 0))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Replaces the user's achievement state for one game.
+pub async fn achievements(
+    db: &Pool<Sqlite>,
+    steam_id: &str,
+    app_id: u32,
+    stats: &PlayerStats,
+) -> Result<(), sqlx::Error> {
+    let Some(achievements) = &stats.achievements else {
+        return Ok(());
+    };
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("DELETE FROM user_achievements WHERE steam_id = ? AND appid = ?")
+        .bind(steam_id)
+        .bind(app_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for achievement in achievements {
+        sqlx::query(
+            "INSERT INTO user_achievements (steam_id, appid, apiname, achieved, unlocktime)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(steam_id)
+        .bind(app_id)
+        .bind(&achievement.apiname)
+        .bind(achievement.achieved)
+        .bind(Some(achievement.unlocktime).filter(|&t| t > 0))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
@@ -0,0 +1,17 @@
+pub mod circuit_breaker;
+pub mod common_games;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod fixtures;
+pub mod history;
+pub mod ingest;
+pub mod mock_upstream;
+pub mod models;
+pub mod redact;
+pub mod retention;
+pub mod routes;
+pub mod single_flight;
+pub mod snapshots;
+pub mod steam_api;
+pub mod steam_id;
@@ -3,44 +3,39 @@ use dotenvy::dotenv;
 use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 
-mod db;
-mod models;
-mod routes;
-mod steam_api;
+use backend::{config, db, retention, routes};
 
-use governor::{Quota, RateLimiter};
-use std::num::NonZeroU32;
 use std::sync::Arc;
-use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
+    let config = Arc::new(config::Config::from_env());
     let pool = db::init_db().await.expect("Failed to initialize database");
 
-    // Create a reqwest client to reuse
-    let client = reqwest::Client::new();
+    // `backend prune`: apply the retention policy once and exit
+    if std::env::args().nth(1).as_deref() == Some("prune") {
+        let report = retention::prune(&pool, &config.retention)
+            .await
+            .expect("Failed to prune snapshots");
+        println!(
+            "Pruned {} snapshot rows ({} daily, {} weekly)",
+            report.total(),
+            report.daily,
+            report.weekly
+        );
+        // Deleting rows only frees pages inside the file; give them back.
+        sqlx::query("VACUUM")
+            .execute(&pool)
+            .await
+            .expect("Failed to vacuum database");
+        return;
+    }
 
-    // Global Steam Rate Limiter: ~200 requests per 5 minutes
-    // We'll set it to 190 to be safe
-    let steam_quota = Quota::with_period(Duration::from_secs(300))
-        .unwrap()
-        .allow_burst(NonZeroU32::new(190).unwrap());
-    let steam_global_limiter = Arc::new(RateLimiter::direct(steam_quota));
+    retention::spawn(pool.clone(), config.retention);
 
-    // User Rate Limiter: 30 requests per minute
-    let user_quota = Quota::with_period(Duration::from_secs(60))
-        .unwrap()
-        .allow_burst(NonZeroU32::new(30).unwrap());
-    let user_limiter = Arc::new(RateLimiter::keyed(user_quota));
-
-    let app_state = db::AppState {
-        db: pool,
-        client,
-        steam_global_limiter,
-        user_limiter,
-    };
+    let app_state = db::AppState::new(pool, config);
 
     let app = Router::new()
         .route("/", get(|| async { "Steam Analyzer Backend Running" }))
@@ -0,0 +1,400 @@
+//! A stand-in for every upstream the backend talks to (Steam Web API, the
+//! store CDN, the community media host and Gemini), serving canned responses
+//! so the API can be exercised offline. Used by the integration tests and by
+//! the `mock-upstream` binary for local development.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// SteamID64 of the canned profile.
+pub const STEAM_ID: &str = "76561197960287930";
+/// A profile that exists but is private: summary only, no games or stats.
+pub const PRIVATE_STEAM_ID: &str = "76561197960265731";
+/// Custom URL name that resolves to [`STEAM_ID`]; any other name is unknown.
+pub const VANITY_NAME: &str = "mockuser";
+/// The only app with canned achievements; others answer "no stats".
+pub const ACHIEVEMENTS_APP_ID: u32 = 440;
+/// Text of every Gemini candidate.
+pub const GEMINI_TEXT: &str = "This is a canned response from the mock Gemini API.";
+
+/// Failure injection, adjustable while the server runs.
+#[derive(Debug, Clone, Default)]
+pub struct MockOptions {
+    /// Delay added before every response.
+    pub latency: Duration,
+    /// Answer every Nth request with 429 and `Retry-After: 1`.
+    pub rate_limit_every: Option<u32>,
+    /// Answer every request with this status instead of the canned body.
+    pub fail_status: Option<u16>,
+    /// Only fail the first N requests, then recover.
+    pub fail_first: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct MockUpstream {
+    options: Mutex<MockOptions>,
+    /// Requests seen per path, including injected failures.
+    hits: Mutex<HashMap<String, usize>>,
+    total: Mutex<u32>,
+}
+
+impl MockUpstream {
+    pub fn new(options: MockOptions) -> Self {
+        MockUpstream {
+            options: Mutex::new(options),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_options(&self, options: MockOptions) {
+        *self.options.lock().unwrap() = options;
+    }
+
+    /// Requests received for paths starting with `prefix`.
+    pub fn hits(&self, prefix: &str) -> usize {
+        self.hits
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(path, _)| path.starts_with(prefix))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn total_hits(&self) -> usize {
+        self.hits("/")
+    }
+}
+
+/// Binds `addr` and serves the mock in the background. Pass port 0 to get a
+/// free port; the bound address is returned.
+pub async fn spawn(
+    addr: SocketAddr,
+    options: MockOptions,
+) -> std::io::Result<(SocketAddr, Arc<MockUpstream>)> {
+    let mock = Arc::new(MockUpstream::new(options));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let app = router(mock.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.ok();
+    });
+    Ok((local_addr, mock))
+}
+
+pub fn router(mock: Arc<MockUpstream>) -> Router {
+    Router::new()
+        .route(
+            "/ISteamUser/GetPlayerSummaries/v0002/",
+            get(player_summaries),
+        )
+        .route(
+            "/ISteamUser/ResolveVanityURL/v0001/",
+            get(resolve_vanity_url),
+        )
+        .route("/ISteamUser/GetFriendList/v0001/", get(friend_list))
+        .route("/IPlayerService/GetOwnedGames/v0001/", get(owned_games))
+        .route(
+            "/IPlayerService/GetRecentlyPlayedGames/v0001/",
+            get(recently_played_games),
+        )
+        .route(
+            "/ISteamUserStats/GetPlayerAchievements/v0001/",
+            get(player_achievements),
+        )
+        .route("/store_item_assets/steam/apps/:appid/:file", get(image))
+        .route(
+            "/steamcommunity/public/images/apps/:appid/:file",
+            get(image),
+        )
+        .route("/v1beta/models/:model", post(generate_content))
+        .layer(middleware::from_fn_with_state(mock, inject_failures))
+}
+
+/// Counts the request, then applies latency, rate limiting, forced failures
+/// and the Steam key check before the canned handler runs.
+async fn inject_failures(
+    State(mock): State<Arc<MockUpstream>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    *mock.hits.lock().unwrap().entry(path.clone()).or_default() += 1;
+    let sequence = {
+        let mut total = mock.total.lock().unwrap();
+        *total += 1;
+        *total
+    };
+    let options = mock.options.lock().unwrap().clone();
+
+    if !options.latency.is_zero() {
+        tokio::time::sleep(options.latency).await;
+    }
+
+    if let Some(every) = options.rate_limit_every.filter(|n| *n > 0) {
+        if sequence % every == 0 {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, "1")],
+                "Too Many Requests",
+            )
+                .into_response();
+        }
+    }
+
+    let failing = options.fail_first.is_none_or(|n| sequence <= n);
+    if let Some(status) = options.fail_status.filter(|_| failing) {
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        return (status, "Injected failure").into_response();
+    }
+
+    // The real Web API answers a missing key with an HTML 403.
+    let query = request.uri().query().unwrap_or_default();
+    let has_key = query.split('&').any(|pair| pair.starts_with("key="));
+    if path.starts_with("/I") && !has_key {
+        return (StatusCode::FORBIDDEN, "<html>Forbidden</html>").into_response();
+    }
+
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct SummariesQuery {
+    #[serde(default)]
+    steamids: String,
+}
+
+/// Every 17-digit id gets a profile; anything else is silently dropped, the
+/// same as Steam does for unknown ids.
+async fn player_summaries(Query(query): Query<SummariesQuery>) -> Json<Value> {
+    let players: Vec<Value> = query
+        .steamids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| id.len() == 17 && id.bytes().all(|b| b.is_ascii_digit()))
+        // Like Steam, ignore anything past the first hundred
+        .take(100)
+        .map(player)
+        .collect();
+    Json(json!({ "response": { "players": players } }))
+}
+
+fn player(steam_id: &str) -> Value {
+    let suffix = &steam_id[steam_id.len() - 4..];
+    let visibility = if steam_id == PRIVATE_STEAM_ID { 1 } else { 3 };
+    json!({
+        "steamid": steam_id,
+        "communityvisibilitystate": visibility,
+        "profilestate": 1,
+        "personaname": format!("Mock Player {}", suffix),
+        "profileurl": format!("https://steamcommunity.com/profiles/{}/", steam_id),
+        "avatar": "https://avatars.steamstatic.com/mock.jpg",
+        "avatarmedium": "https://avatars.steamstatic.com/mock_medium.jpg",
+        "avatarfull": "https://avatars.steamstatic.com/mock_full.jpg",
+        "personastate": 1,
+        "lastlogoff": 1700000000,
+        "timecreated": 1100000000,
+        "loccountrycode": "US"
+    })
+}
+
+#[derive(Deserialize)]
+struct VanityQuery {
+    #[serde(default)]
+    vanityurl: String,
+}
+
+async fn resolve_vanity_url(Query(query): Query<VanityQuery>) -> Json<Value> {
+    if query.vanityurl.eq_ignore_ascii_case(VANITY_NAME) {
+        Json(json!({ "response": { "steamid": STEAM_ID, "success": 1 } }))
+    } else {
+        Json(json!({ "response": { "success": 42, "message": "No match" } }))
+    }
+}
+
+#[derive(Deserialize)]
+struct OwnedGamesQuery {
+    #[serde(default)]
+    steamid: String,
+}
+
+async fn owned_games(Query(query): Query<OwnedGamesQuery>) -> Json<Value> {
+    // What Steam sends for a private library
+    if query.steamid == PRIVATE_STEAM_ID {
+        return Json(json!({ "response": {} }));
+    }
+    Json(json!({
+        "response": {
+            "game_count": 3,
+            "games": [
+                {
+                    "appid": 440,
+                    "name": "Team Fortress 2",
+                    "playtime_forever": 5400,
+                    "playtime_2weeks": 120,
+                    "img_icon_url": "e3f595a92552da3d664ad00277fad2107345f743",
+                    "has_community_visible_stats": true,
+                    "rtime_last_played": 1700000000
+                },
+                {
+                    "appid": 570,
+                    "name": "Dota 2",
+                    "playtime_forever": 12000,
+                    "img_icon_url": "0bbb630d63262dd66d2fdd0f7d37e8661a410075",
+                    "has_community_visible_stats": true,
+                    "rtime_last_played": 1690000000
+                },
+                {
+                    "appid": 620,
+                    "name": "Portal 2",
+                    "playtime_forever": 900,
+                    "img_icon_url": "2e478fc6874d06ae5baf0d147f6f21203291aa02",
+                    "has_community_visible_stats": true,
+                    "rtime_last_played": 1600000000
+                }
+            ]
+        }
+    }))
+}
+
+/// Only [`STEAM_ID`] has a visible friend list (including the private
+/// profile); Steam answers a bare 401 for everyone else's.
+async fn friend_list(Query(query): Query<OwnedGamesQuery>) -> Response {
+    if query.steamid != STEAM_ID {
+        let body = "<html><head><title>Unauthorized</title></head></html>";
+        return (StatusCode::UNAUTHORIZED, body).into_response();
+    }
+    let friends: Vec<Value> = [
+        ("76561197960265729", 1500000000),
+        ("76561197960265730", 1600000000),
+        (PRIVATE_STEAM_ID, 1650000000),
+    ]
+    .iter()
+    .map(|(steam_id, since)| {
+        json!({ "steamid": steam_id, "relationship": "friend", "friend_since": since })
+    })
+    .collect();
+    Json(json!({ "friendslist": { "friends": friends } })).into_response()
+}
+
+/// Takes the same `steamid` parameter as GetOwnedGames.
+async fn recently_played_games(Query(query): Query<OwnedGamesQuery>) -> Json<Value> {
+    if query.steamid == PRIVATE_STEAM_ID {
+        return Json(json!({ "response": {} }));
+    }
+    Json(json!({
+        "response": {
+            "total_count": 1,
+            "games": [
+                {
+                    "appid": 440,
+                    "name": "Team Fortress 2",
+                    "playtime_2weeks": 120,
+                    "playtime_forever": 5400,
+                    "img_icon_url": "e3f595a92552da3d664ad00277fad2107345f743"
+                }
+            ]
+        }
+    }))
+}
+
+#[derive(Deserialize)]
+struct AchievementsQuery {
+    appid: Option<u32>,
+    #[serde(default)]
+    steamid: String,
+}
+
+async fn player_achievements(Query(query): Query<AchievementsQuery>) -> Response {
+    if query.steamid == PRIVATE_STEAM_ID {
+        let body = json!({
+            "playerstats": { "error": "Profile is not public", "success": false }
+        });
+        return (StatusCode::FORBIDDEN, Json(body)).into_response();
+    }
+    if query.appid != Some(ACHIEVEMENTS_APP_ID) {
+        // Steam pairs this body with a 400.
+        let body = json!({
+            "playerstats": { "error": "Requested app has no stats", "success": false }
+        });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+
+    Json(json!({
+        "playerstats": {
+            "steamID": query.steamid,
+            "gameName": "Team Fortress 2",
+            "achievements": [
+                {
+                    "apiname": "TF_PLAY_GAME_EVERYCLASS",
+                    "achieved": 1,
+                    "unlocktime": 1300000000,
+                    "name": "Head of the Class",
+                    "description": "Play a complete round with every class."
+                },
+                {
+                    "apiname": "TF_GET_HEALPOINTS",
+                    "achieved": 0,
+                    "unlocktime": 0,
+                    "name": "Team Doctor",
+                    "description": "Accumulate 25000 heal points as a Medic."
+                }
+            ],
+            "success": true
+        }
+    }))
+    .into_response()
+}
+
+/// Banners and icons for every app: `header.jpg` and icons exist, the hero
+/// image is missing so the proxy's fallback path gets used.
+async fn image(Path((_appid, file)): Path<(String, String)>) -> Response {
+    if file == "library_hero.jpg" {
+        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+    }
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "image/jpeg")],
+        Body::from(format!("mock image {}", file)),
+    )
+        .into_response()
+}
+
+async fn generate_content(
+    Path(model): Path<String>,
+    headers: HeaderMap,
+    Json(_request): Json<Value>,
+) -> Response {
+    if !model.ends_with(":generateContent") {
+        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+    }
+    if headers.get("x-goog-api-key").is_none() {
+        let body = json!({
+            "error": { "code": 403, "message": "Missing API key", "status": "PERMISSION_DENIED" }
+        });
+        return (StatusCode::FORBIDDEN, Json(body)).into_response();
+    }
+
+    Json(json!({
+        "candidates": [{
+            "content": { "parts": [{ "text": GEMINI_TEXT }], "role": "model" },
+            "finishReason": "STOP",
+            "index": 0
+        }],
+        "modelVersion": model.trim_end_matches(":generateContent")
+    }))
+    .into_response()
+}
@@ -0,0 +1,149 @@
+use reqwest::Url;
+use std::env;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Query parameters that carry credentials in upstream URLs.
+const SECRET_PARAMS: &[&str] = &["key", "api_key", "access_token"];
+
+/// Environment variables holding secrets whose values must never be echoed.
+const SECRET_ENV_VARS: &[&str] = &["STEAM_API_KEY", "GEMINI_API_KEY"];
+
+/// Masks secrets in free text before it is logged or sent to a client:
+/// credential query parameters (`key=...`) and the literal values of our
+/// configured API keys, wherever they appear.
+pub fn redact(text: &str) -> String {
+    let mut redacted = redact_query_params(text);
+    for var in SECRET_ENV_VARS {
+        if let Ok(secret) = env::var(var) {
+            // Very short values would mask unrelated text and aren't real keys
+            if secret.len() >= 8 {
+                redacted = redacted.replace(&secret, REDACTED);
+            }
+        }
+    }
+    redacted
+}
+
+/// Masks credential query parameters in a URL in place.
+pub fn redact_url(url: &mut Url) {
+    if !url.query_pairs().any(|(name, _)| is_secret_param(&name)) {
+        return;
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_secret_param(&name) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+}
+
+/// reqwest errors embed the request URL in their `Display` output; mask any
+/// credentials in it so the error can be logged or wrapped safely.
+pub fn reqwest_error(mut e: reqwest::Error) -> reqwest::Error {
+    if let Some(url) = e.url_mut() {
+        redact_url(url);
+    }
+    e
+}
+
+pub(crate) fn is_secret_param(name: &str) -> bool {
+    SECRET_PARAMS.iter().any(|p| name.eq_ignore_ascii_case(p))
+}
+
+/// Replaces the value of `key=...`-style parameters found anywhere in `text`.
+fn redact_query_params(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(value_start) = find_secret_value(rest) {
+        out.push_str(&rest[..value_start]);
+        out.push_str(REDACTED);
+        let value_len = rest[value_start..]
+            .find(|c: char| {
+                c == '&' || c == '#' || c == '"' || c == '\'' || c == ')' || c.is_whitespace()
+            })
+            .unwrap_or(rest.len() - value_start);
+        rest = &rest[value_start + value_len..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Byte offset of the value of the next `?key=`/`&key=` style parameter.
+fn find_secret_value(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut search_from = 0;
+    while let Some(eq) = text[search_from..].find('=').map(|i| i + search_from) {
+        let name_start = text[..eq]
+            .rfind(|c: char| c == '?' || c == '&' || c.is_whitespace())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let preceded_by_separator = name_start > 0 && matches!(bytes[name_start - 1], b'?' | b'&');
+        if preceded_by_separator && is_secret_param(&text[name_start..eq]) {
+            return Some(eq + 1);
+        }
+        search_from = eq + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use crate::steam_api::SteamApiError;
+    use axum::response::IntoResponse;
+
+    const KEY: &str = "0123456789ABCDEF0123456789ABCDEF";
+
+    #[test]
+    fn masks_key_query_params() {
+        let text = format!(
+            "error sending request for url (http://api.steampowered.com/x?key={}&steamid=1)",
+            KEY
+        );
+        let redacted = redact(&text);
+        assert!(!redacted.contains(KEY));
+        assert!(redacted.contains("key=[REDACTED]&steamid=1"));
+    }
+
+    #[test]
+    fn leaves_unrelated_params_alone() {
+        let text = "http://example.com/?monkey=1&keyboard=2&steamid=3";
+        assert_eq!(redact(text), text);
+    }
+
+    #[tokio::test]
+    async fn steam_errors_never_contain_the_api_key() {
+        // Nothing listens on port 1, so this fails with a connection error
+        // whose message would normally include the full URL.
+        let url = format!(
+            "http://127.0.0.1:1/ISteamUser/GetPlayerSummaries/v0002/?key={}&steamids=1",
+            KEY
+        );
+        let raw = reqwest::Client::new().get(&url).send().await.unwrap_err();
+        assert!(
+            raw.to_string().contains(KEY),
+            "precondition: reqwest leaks the URL"
+        );
+
+        let error = SteamApiError::from(raw);
+        assert!(!error.to_string().contains(KEY));
+        assert!(!format!("{:?}", error).contains(KEY));
+
+        let api_error = ApiError::from(error);
+        assert!(!api_error.to_string().contains(KEY));
+        let body = axum::body::to_bytes(api_error.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains(KEY));
+    }
+}
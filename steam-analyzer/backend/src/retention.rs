@@ -0,0 +1,190 @@
+use crate::config::RetentionPolicy;
+use sqlx::{Pool, Sqlite};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PruneReport {
+    /// Rows removed while thinning to one per day.
+    pub daily: u64,
+    /// Rows removed while thinning to one per week.
+    pub weekly: u64,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> u64 {
+        self.daily + self.weekly
+    }
+}
+
+/// Deletes snapshots the policy no longer needs. Within each period the
+/// newest row per profile and data type is kept, so the latest snapshot is
+/// never removed and history stays usable for playtime deltas. Weeks run
+/// Monday to Sunday, like the weekly history buckets, including weeks that
+/// span New Year.
+pub async fn prune(
+    db: &Pool<Sqlite>,
+    policy: &RetentionPolicy,
+) -> Result<PruneReport, sqlx::Error> {
+    let keep_all = format!("-{} days", policy.keep_all_days);
+    let keep_daily = format!("-{} days", policy.keep_daily_days);
+
+    let daily = sqlx::query(
+        "DELETE FROM snapshots WHERE id IN (
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (
+                    PARTITION BY steam_id, data_type, date(created_at)
+                    ORDER BY created_at DESC, id DESC
+                ) AS rank
+                FROM snapshots
+                WHERE created_at < datetime('now', ?) AND created_at >= datetime('now', ?)
+            ) WHERE rank > 1
+        )",
+    )
+    .bind(&keep_all)
+    .bind(&keep_daily)
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    let weekly = sqlx::query(
+        "DELETE FROM snapshots WHERE id IN (
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (
+                    PARTITION BY steam_id, data_type, date(created_at, 'weekday 0', '-6 days')
+                    ORDER BY created_at DESC, id DESC
+                ) AS rank
+                FROM snapshots
+                WHERE created_at < datetime('now', ?)
+            ) WHERE rank > 1
+        )",
+    )
+    .bind(&keep_daily)
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(PruneReport { daily, weekly })
+}
+
+/// Runs `prune` every `policy.interval` for the lifetime of the process. An
+/// interval of zero disables the job; `prune` can still be run by hand.
+pub fn spawn(db: Pool<Sqlite>, policy: RetentionPolicy) {
+    if policy.interval.is_zero() {
+        println!("🧹 Snapshot retention job disabled (RETENTION_INTERVAL_SECS=0)");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(policy.interval);
+        loop {
+            ticker.tick().await;
+            match prune(&db, &policy).await {
+                Ok(report) => println!(
+                    "🧹 Snapshot retention pruned {} rows ({} daily, {} weekly)",
+                    report.total(),
+                    report.daily,
+                    report.weekly
+                ),
+                Err(e) => eprintln!("Snapshot retention failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use std::time::Duration;
+
+    const POLICY: RetentionPolicy = RetentionPolicy {
+        keep_all_days: 7,
+        keep_daily_days: 90,
+        interval: Duration::from_secs(60),
+    };
+
+    async fn insert(db: &Pool<Sqlite>, data_type: &str, created_at: &str) {
+        sqlx::query(&format!(
+            "INSERT INTO snapshots (steam_id, data_type, json_data, created_at)
+             VALUES ('76561197960287930', ?, '{{}}', {})",
+            created_at
+        ))
+        .bind(data_type)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    async fn surviving(db: &Pool<Sqlite>, data_type: &str) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT CAST(created_at AS TEXT) FROM snapshots WHERE data_type = ?
+             ORDER BY created_at",
+        )
+        .bind(data_type)
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn thins_old_snapshots_to_one_per_day_then_week() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite:{}?mode=rwc", dir.path().join("test.db").display());
+        let pool = db::connect(&url).await.unwrap();
+        sqlx::query("INSERT INTO users (steam_id, username) VALUES ('76561197960287930', 'x')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Within keep_all_days: everything stays
+        for hour in 1..=3 {
+            insert(
+                &pool,
+                "owned_games",
+                &format!("datetime('now', '-1 days', '-{} hours')", hour),
+            )
+            .await;
+        }
+        // Two days inside the daily window, several rows each
+        for days in [10, 20] {
+            for hour in 1..=3 {
+                insert(
+                    &pool,
+                    "owned_games",
+                    &format!("datetime(date('now', '-{} days'), '+{} hours')", days, hour),
+                )
+                .await;
+            }
+        }
+        // Past keep_daily_days: Monday 2019-12-30 to Sunday 2020-01-05 is one
+        // week across New Year, then the next Monday starts another
+        for day in [
+            "2019-12-30 10:00:00",
+            "2019-12-31 10:00:00",
+            "2020-01-01 10:00:00",
+            "2020-01-05 10:00:00",
+            "2020-01-06 10:00:00",
+        ] {
+            insert(&pool, "owned_games", &format!("'{}'", day)).await;
+        }
+        // A single old row of another type is the newest of its kind
+        insert(&pool, "player_summary", "'2019-06-01 10:00:00'").await;
+
+        let report = prune(&pool, &POLICY).await.unwrap();
+        assert_eq!(report.daily, 4);
+        assert_eq!(report.weekly, 3);
+
+        let kept = surviving(&pool, "owned_games").await;
+        assert_eq!(kept.len(), 7);
+        assert_eq!(kept[0], "2020-01-05 10:00:00");
+        assert_eq!(kept[1], "2020-01-06 10:00:00");
+        // Newest row of each daily-window day
+        assert!(kept[2].ends_with("03:00:00"));
+        assert!(kept[3].ends_with("03:00:00"));
+        assert_eq!(
+            surviving(&pool, "player_summary").await,
+            ["2019-06-01 10:00:00"]
+        );
+
+        // Nothing left to thin
+        assert_eq!(prune(&pool, &POLICY).await.unwrap().total(), 0);
+    }
+}
@@ -1,4 +1,7 @@
+use super::check_user_rate_limit;
 use crate::db::AppState;
+use crate::error::ApiError;
+use crate::redact;
 use axum::{
     extract::{ConnectInfo, State},
     routing::post,
@@ -8,6 +11,7 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use std::env;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 #[derive(Deserialize)]
 struct GenerateRequest {
@@ -22,19 +26,17 @@ async fn generate_content(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(payload): Json<GenerateRequest>,
-) -> Json<Value> {
+) -> Result<Json<Value>, ApiError> {
     let api_key = env::var("GEMINI_API_KEY").unwrap_or_default();
 
     if api_key.is_empty() {
-        return Json(json!({"error": "Server configuration error: Missing Google API Key"}));
+        return Err(ApiError::NotConfigured(
+            "Server configuration error: Missing Google API Key".to_string(),
+        ));
     }
 
     // 0. Check User Rate Limit (IP based spam protection)
-    if let Err(_) = state.user_limiter.check_key(&addr.ip()) {
-        return Json(json!({
-            "error": "Too many requests. Please try again later."
-        }));
-    }
+    check_user_rate_limit(&state, addr.ip())?;
 
     // 1. Check Rate Limits (RPM: 5, RPD: 20)
     let pool = &state.db;
@@ -48,9 +50,11 @@ async fn generate_content(
     .unwrap_or(0);
 
     if rpm_count >= 5 {
-        return Json(json!({
-            "error": "Rate limit exceeded (5 requests/minute). Please try again in a moment."
-        }));
+        return Err(ApiError::RateLimited {
+            message: "Rate limit exceeded (5 requests/minute). Please try again in a moment."
+                .to_string(),
+            retry_after: window_reset_in(pool, "+1 minute", "-1 minute").await,
+        });
     }
 
     // Check records in the last 24 hours (RPD)
@@ -62,14 +66,17 @@ async fn generate_content(
     .unwrap_or(0);
 
     if rpd_count >= 20 {
-        return Json(json!({
-            "error": "Daily rate limit exceeded (20 requests/day). Resets rolling 24h."
-        }));
+        return Err(ApiError::RateLimited {
+            message: "Daily rate limit exceeded (20 requests/day). Resets rolling 24h.".to_string(),
+            retry_after: window_reset_in(pool, "+1 day", "-1 day").await,
+        });
     }
 
+    // The key goes in a header rather than the query string so it can't leak
+    // through URLs in error messages.
     let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash-preview-09-2025:generateContent?key={}",
-        api_key
+        "{}/v1beta/models/gemini-2.5-flash-preview-09-2025:generateContent",
+        state.config.upstreams.gemini
     );
 
     let client = &state.client;
@@ -79,7 +86,14 @@ async fn generate_content(
         }]
     });
 
-    let res = client.post(&url).json(&request_body).send().await;
+    let res = client
+        .post(&url)
+        .header("x-goog-api-key", &api_key)
+        .timeout(state.config.timeouts.gemini)
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(redact::reqwest_error);
 
     match res {
         Ok(response) => {
@@ -99,14 +113,44 @@ async fn generate_content(
                         // Let's normalize it to return { "text": "..." } or similar,
                         // but to keep it versatile let's return the full Gemni response structure for now
                         // and let the frontend helper parse it.
-                        Json(data)
+                        Ok(Json(data))
                     }
-                    Err(_) => Json(json!({"error": "Failed to parse AI response"})),
+                    Err(e) if e.is_timeout() => Err(ApiError::UpstreamTimeout(
+                        "The AI provider did not respond in time".to_string(),
+                    )),
+                    Err(_) => Err(ApiError::AiProviderError(
+                        "Failed to parse AI response".to_string(),
+                    )),
                 }
             } else {
-                Json(json!({"error": format!("AI Provider Error: {}", response.status())}))
+                Err(ApiError::AiProviderError(format!(
+                    "AI Provider Error: {}",
+                    response.status()
+                )))
             }
         }
-        Err(e) => Json(json!({"error": format!("Failed to reach AI provider: {}", e)})),
+        Err(e) if e.is_timeout() => Err(ApiError::UpstreamTimeout(
+            "The AI provider did not respond in time".to_string(),
+        )),
+        Err(e) => Err(ApiError::AiProviderError(format!(
+            "Failed to reach AI provider: {}",
+            e
+        ))),
     }
 }
+
+/// Seconds until the oldest logged request falls out of a rolling window, i.e.
+/// when the next slot frees up. `expiry` and `window` are SQLite modifiers.
+async fn window_reset_in(pool: &sqlx::SqlitePool, expiry: &str, window: &str) -> Duration {
+    let secs: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(CAST(strftime('%s', MIN(timestamp), ?) AS INTEGER) - CAST(strftime('%s', 'now') AS INTEGER), 0)
+         FROM gemini_usage_logs WHERE timestamp > datetime('now', ?)",
+    )
+    .bind(expiry)
+    .bind(window)
+    .fetch_one(pool)
+    .await
+    .unwrap_or(0);
+
+    Duration::from_secs(secs.max(1) as u64)
+}
@@ -0,0 +1,36 @@
+use crate::db::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde_json::{json, Value};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/health", get(health))
+}
+
+/// Liveness plus upstream state. Only a broken database makes this non-200:
+/// with Steam down we still serve cached data.
+async fn health(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let database_ok = sqlx::query("SELECT 1").execute(&state.db).await.is_ok();
+    let circuit = state.steam_breaker.status();
+
+    let status = if !database_ok {
+        "down"
+    } else if circuit.state != "closed" {
+        "degraded"
+    } else {
+        "ok"
+    };
+    let code = if database_ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        code,
+        Json(json!({
+            "status": status,
+            "database": if database_ok { "ok" } else { "unavailable" },
+            "steam": { "circuit": circuit },
+        })),
+    )
+}
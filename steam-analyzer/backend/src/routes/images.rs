@@ -7,6 +7,7 @@ use axum::{
 use axum::http::{StatusCode, header};
 use axum::body::Body;
 use crate::db::AppState;
+use crate::error::ApiError;
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -19,22 +20,44 @@ async fn get_banner_image(
     Path(appid): Path<String>,
 ) -> impl IntoResponse {
     // Try hero first
-    let hero_url = format!("https://shared.akamai.steamstatic.com/store_item_assets/steam/apps/{}/library_hero.jpg", appid);
-    
-    if let Ok(resp) = state.client.get(&hero_url).send().await {
-        if resp.status().is_success() {
-             let stream = Body::from_stream(resp.bytes_stream());
-             return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
+    let hero_url = format!(
+        "{}/store_item_assets/steam/apps/{}/library_hero.jpg",
+        state.config.upstreams.steam_cdn, appid
+    );
+
+    match state
+        .client
+        .get(&hero_url)
+        .timeout(state.config.timeouts.cdn)
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {
+            let stream = Body::from_stream(resp.bytes_stream());
+            return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
         }
+        Err(e) if e.is_timeout() => return cdn_timeout(),
+        _ => {}
     }
 
     // Try header second
-    let header_url = format!("https://shared.akamai.steamstatic.com/store_item_assets/steam/apps/{}/header.jpg", appid);
-    if let Ok(resp) = state.client.get(&header_url).send().await {
-         if resp.status().is_success() {
-             let stream = Body::from_stream(resp.bytes_stream());
-             return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
+    let header_url = format!(
+        "{}/store_item_assets/steam/apps/{}/header.jpg",
+        state.config.upstreams.steam_cdn, appid
+    );
+    match state
+        .client
+        .get(&header_url)
+        .timeout(state.config.timeouts.cdn)
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {
+            let stream = Body::from_stream(resp.bytes_stream());
+            return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
         }
+        Err(e) if e.is_timeout() => return cdn_timeout(),
+        _ => {}
     }
 
     // Fallback: Return empty 200 OK with no content type or a specific placeholder
@@ -54,13 +77,24 @@ async fn get_icon_image(
     State(state): State<AppState>,
     Path((appid, hash)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    let icon_url = format!("https://media.steampowered.com/steamcommunity/public/images/apps/{}/{}.jpg", appid, hash);
+    let icon_url = format!(
+        "{}/steamcommunity/public/images/apps/{}/{}.jpg",
+        state.config.upstreams.steam_media, appid, hash
+    );
 
-    if let Ok(resp) = state.client.get(&icon_url).send().await {
-        if resp.status().is_success() {
-             let stream = Body::from_stream(resp.bytes_stream());
-             return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
+    match state
+        .client
+        .get(&icon_url)
+        .timeout(state.config.timeouts.cdn)
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {
+            let stream = Body::from_stream(resp.bytes_stream());
+            return (StatusCode::OK, [(header::CONTENT_TYPE, "image/jpeg")], stream).into_response();
         }
+        Err(e) if e.is_timeout() => return cdn_timeout(),
+        _ => {}
     }
 
     // Fallback 1x1 transparent gif
@@ -72,3 +106,9 @@ async fn get_icon_image(
 
     (StatusCode::OK, [(header::CONTENT_TYPE, "image/gif")], Body::from(transparent_gif)).into_response()
 }
+
+/// A hung CDN gets a 504 rather than the placeholder, so clients can tell
+/// "no image" from "try again".
+fn cdn_timeout() -> axum::response::Response {
+    ApiError::UpstreamTimeout("The image CDN did not respond in time".to_string()).into_response()
+}
@@ -1,14 +1,52 @@
 use crate::db::AppState;
+use crate::error::ApiError;
 use axum::Router;
+use governor::{
+    clock::{Clock, DefaultClock},
+    InsufficientCapacity,
+};
+use std::net::IpAddr;
+use std::num::NonZeroU32;
 
 pub mod gemini;
+pub mod health;
 pub mod images;
 pub mod steam;
 // pub mod users; // later
 
 pub fn api_router() -> Router<AppState> {
     Router::new()
+        .merge(health::router())
         .nest("/steam", steam::router())
         .nest("/images", images::router())
         .nest("/ai", gemini::router())
 }
+
+/// Per-IP spam protection shared by every route that costs upstream quota.
+pub(crate) fn check_user_rate_limit(state: &AppState, ip: IpAddr) -> Result<(), ApiError> {
+    state.user_limiter.check_key(&ip).map_err(|not_until| {
+        ApiError::too_many_requests(not_until.wait_time_from(DefaultClock::default().now()))
+    })
+}
+
+/// Charges `extra` more tokens to a request that fans out into several Steam
+/// calls, so one request can't spend more quota than its client may. All or
+/// nothing: a refusal charges nothing.
+pub(crate) fn charge_user_rate_limit(
+    state: &AppState,
+    ip: IpAddr,
+    extra: u32,
+) -> Result<(), ApiError> {
+    let Some(extra) = NonZeroU32::new(extra) else {
+        return Ok(());
+    };
+    match state.user_limiter.check_key_n(&ip, extra) {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(not_until)) => Err(ApiError::too_many_requests(
+            not_until.wait_time_from(DefaultClock::default().now()),
+        )),
+        Err(InsufficientCapacity(_)) => Err(ApiError::BadRequest(
+            "This request needs more Steam calls than one client may make per minute".to_string(),
+        )),
+    }
+}
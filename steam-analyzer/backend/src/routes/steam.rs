@@ -1,222 +1,978 @@
-use crate::{db::AppState, steam_api};
+use super::{charge_user_rate_limit, check_user_rate_limit};
+use crate::{
+    common_games,
+    db::AppState,
+    error::ApiError,
+    history::{self, Granularity},
+    ingest,
+    snapshots::{self, Snapshot},
+    steam_api::{
+        FriendListResponse, OwnedGames, PlayerStatsResponse, PlayerSummaries, PlayerSummary,
+        RecentlyPlayedGames, SteamApiError, SteamResponse, MAX_SUMMARIES_PER_CALL,
+    },
+    steam_id::{self, SteamId, SteamIdInput},
+};
 use axum::{
-    extract::{ConnectInfo, Path, State}, // Added ConnectInfo
-    routing::get,
+    extract::{ConnectInfo, Path, Query, State}, // Added ConnectInfo
+    routing::{get, post},
     Json,
     Router,
 };
+use chrono::{Days, NaiveDate, Utc};
+use futures::future::join_all;
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::{json, Value};
-use sqlx::Row;
-use std::env;
-use std::net::SocketAddr; // Added SocketAddr
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr}; // Added SocketAddr
+use std::time::Duration;
+
+#[derive(Deserialize, Default)]
+struct RefreshParams {
+    /// Skip the cache and go to Steam (still subject to the global limiter).
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct HistoryParams {
+    /// First day (YYYY-MM-DD, UTC), defaults to 30 days before `to`.
+    from: Option<String>,
+    /// Last day (YYYY-MM-DD, UTC, inclusive), defaults to today.
+    to: Option<String>,
+    /// `daily` or `weekly` (default).
+    bucket: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SummariesRequest {
+    steamids: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct CommonGamesParams {
+    /// Comma-separated ids in any form `/user/:id` accepts.
+    #[serde(default)]
+    ids: String,
+}
+
+/// Most players one common-games request may compare.
+const MAX_COMMON_GAMES_PLAYERS: usize = 10;
+
+/// Most ids one batch request may ask for: ten Steam calls when none are
+/// cached, each charged to the client's rate limit.
+const MAX_BATCH_IDS: usize = 1000;
 
 pub fn router() -> Router<AppState> {
     Router::new()
+        .route("/users/summaries", post(get_player_summaries))
+        .route("/common-games", get(get_common_games))
         .route("/user/:id", get(get_user_steam_data))
+        .route("/user/:id/recent", get(get_recent_games))
+        .route("/user/:id/friends", get(get_friends))
+        .route("/user/:id/history", get(get_playtime_history))
         .route(
             "/user/:id/achievements/:appid",
             get(get_player_achievements),
         )
 }
 
-async fn get_player_achievements(
-    State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>, // Extract IP
-    Path((steam_id, app_id)): Path<(String, String)>,
-) -> Json<Value> {
-    // Check User Rate Limit
-    if let Err(_) = state.user_limiter.check_key(&addr.ip()) {
-        return Json(json!({
-            "error": "Too many requests. Please try again later."
-        }));
+/// Turns whatever the client sent (SteamID64/3, legacy ID, profile URL or
+/// custom URL name) into a SteamID64. Malformed input is rejected before
+/// any Steam quota is spent; custom URLs cost one ResolveVanityURL call
+/// unless a resolution younger than its TTL is cached.
+async fn resolve_steam_id(state: &AppState, raw: &str) -> Result<SteamId, ApiError> {
+    let input = steam_id::parse(raw).map_err(|e| ApiError::InvalidSteamId(e.to_string()))?;
+    resolve_input(state, input).await
+}
+
+async fn resolve_input(state: &AppState, input: SteamIdInput) -> Result<SteamId, ApiError> {
+    let vanity = match input {
+        SteamIdInput::Id(id) => return Ok(id),
+        SteamIdInput::Vanity(name) => name,
+    };
+
+    if let Some(id) = cached_vanity_url(state, &vanity).await {
+        return Ok(id);
     }
 
-    // Check for cached achievements
-    // Ideally we cache these too, but for now let's just fetch to get it working first
-    // Then we can add caching logic similar to summary/games
+    let key = (vanity.clone(), "vanity_url".to_string());
+    state
+        .in_flight
+        .run(key, || resolve_vanity_url(state, &vanity))
+        .await
+}
 
-    // Try cache first
-    let cached_achievements = sqlx::query(
-        "SELECT json_data FROM snapshots 
-         WHERE steam_id = ? AND data_type = ? 
-         ORDER BY created_at DESC LIMIT 1",
+/// The SteamID64 a custom URL resolved to, if that was within its TTL.
+async fn cached_vanity_url(state: &AppState, vanity: &str) -> Option<SteamId> {
+    let cached: Option<String> = sqlx::query_scalar(
+        "SELECT steam_id FROM vanity_urls WHERE vanity = ? AND resolved_at > datetime('now', ?)",
     )
-    .bind(&steam_id)
-    .bind(format!("achievements_{}", app_id))
+    .bind(vanity)
+    .bind(format!(
+        "-{} seconds",
+        state.config.cache_ttl.vanity_url.as_secs()
+    ))
     .fetch_optional(&state.db)
     .await
     .unwrap_or(None);
+    cached
+        .and_then(|id| id.parse().ok())
+        .and_then(SteamId::from_steam64)
+}
 
-    if let Some(row) = cached_achievements {
-        let json_str: String = row.get("json_data");
-        if let Ok(val) = serde_json::from_str::<Value>(&json_str) {
-            return Json(val);
-        }
-    }
+/// One ResolveVanityURL call. Matches are cached; misses are not, since the
+/// name may be claimed at any time.
+async fn resolve_vanity_url(state: &AppState, vanity: &str) -> Result<SteamId, ApiError> {
+    let data = state.steam.resolve_vanity_url(vanity).await?;
+
+    let id = data
+        .response
+        .steamid
+        .filter(|_| data.response.success == 1)
+        .and_then(|id| id.parse().ok())
+        .and_then(SteamId::from_steam64)
+        .ok_or_else(|| {
+            ApiError::ProfileNotFound(format!("No Steam profile uses the custom URL '{}'", vanity))
+        })?;
 
-    let api_key = env::var("STEAM_API_KEY").unwrap_or_default();
-    // Pass steam_global_limiter
-    match steam_api::fetch_player_achievements(
-        &state.client,
-        &api_key,
+    let _ = sqlx::query(
+        "INSERT INTO vanity_urls (vanity, steam_id) VALUES (?, ?)
+         ON CONFLICT(vanity) DO UPDATE SET steam_id = ?, resolved_at = CURRENT_TIMESTAMP",
+    )
+    .bind(vanity)
+    .bind(id.to_string())
+    .bind(id.to_string())
+    .execute(&state.db)
+    .await;
+
+    Ok(id)
+}
+
+async fn get_player_achievements(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>, // Extract IP
+    Path((raw_id, raw_app_id)): Path<(String, String)>,
+    Query(params): Query<RefreshParams>,
+) -> Result<Json<Value>, ApiError> {
+    // Check User Rate Limit
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let app_id: u32 = raw_app_id
+        .parse()
+        .map_err(|_| ApiError::BadRequest(format!("'{}' is not a Steam app id", raw_app_id)))?;
+    let steam_id = resolve_steam_id(&state, &raw_id).await?.to_string();
+    let data_type = format!("achievements_{}", app_id);
+
+    let achievements = load_cached(
+        &state,
         &steam_id,
-        &app_id,
-        &state.steam_global_limiter,
+        &data_type,
+        state.config.cache_ttl.achievements,
+        params.refresh,
+        // Rows cached before error payloads were rejected don't count
+        |c: &PlayerStatsResponse| c.playerstats.ensure_success().is_ok(),
+        move |state, id| async move { fetch_and_store_achievements(&state, &id, app_id).await },
     )
-    .await
-    {
-        Ok(data) => {
-            // Cache it
-            let json_str = serde_json::to_string(&data).unwrap_or_default();
-            let _ = sqlx::query(
-                "INSERT INTO snapshots (steam_id, data_type, json_data) VALUES (?, ?, ?)",
-            )
-            .bind(&steam_id)
-            .bind(format!("achievements_{}", app_id))
-            .bind(json_str)
-            .execute(&state.db)
-            .await;
-
-            Json(data)
-        }
-        Err(e) => {
-            eprintln!("Failed to fetch achievements: {}", e);
-            Json(json!({"error": "Failed to fetch achievements"}))
-        }
+    .await?;
+    Ok(Json(with_meta(&achievements)))
+}
+
+async fn fetch_and_store_achievements(
+    state: &AppState,
+    steam_id: &str,
+    app_id: u32,
+) -> Result<Snapshot<PlayerStatsResponse>, ApiError> {
+    let data = state
+        .steam
+        .fetch_player_achievements(steam_id, app_id)
+        .await?;
+    // Error payloads (private profile, no stats) must not be cached
+    data.playerstats.ensure_success()?;
+
+    // Cache it
+    let data_type = format!("achievements_{}", app_id);
+    snapshots::store(&state.db, steam_id, &data_type, &data).await;
+    if let Err(e) = ingest::achievements(&state.db, steam_id, app_id, &data.playerstats).await {
+        eprintln!("Failed to normalize achievements: {}", e);
     }
+
+    Ok(Snapshot::fresh(data))
+}
+
+/// Achievements are returned as Steam shaped them, with a `meta` key added.
+fn with_meta(snapshot: &Snapshot<PlayerStatsResponse>) -> Value {
+    let mut body = json!(snapshot.data);
+    body["meta"] = snapshot.meta();
+    body
 }
 
 async fn get_user_steam_data(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>, // Extract IP
-    Path(steam_id): Path<String>,
-) -> Json<Value> {
+    Path(raw_id): Path<String>,
+    Query(params): Query<RefreshParams>,
+) -> Result<Json<Value>, ApiError> {
     // Check User Rate Limit
-    if let Err(_) = state.user_limiter.check_key(&addr.ip()) {
-        return Json(json!({
-            "error": "Too many requests. Please try again later."
-        }));
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let steam_id = resolve_steam_id(&state, &raw_id).await?.to_string();
+
+    // Independent endpoints are fetched concurrently so a cold load costs the
+    // slowest call, not the sum. Each fetch still waits on the global limiter.
+    let (summary, games) = tokio::join!(
+        load_player_summary(&state, &steam_id, params.refresh),
+        load_owned_games(&state, &steam_id, params.refresh),
+    );
+    let summary = summary?;
+    let games = match games {
+        // The summary tells a private profile from merely private game details
+        Err(ApiError::ProfilePrivate(_))
+            if summary
+                .data
+                .response
+                .players
+                .first()
+                .is_some_and(|p| !p.is_public()) =>
+        {
+            return Err(ApiError::ProfilePrivate(
+                "This Steam profile is private. In Steam, open Edit Profile > Privacy \
+                 Settings and set \"My profile\" and \"Game details\" to Public, then try again."
+                    .to_string(),
+            ));
+        }
+        games => games?,
+    };
+
+    Ok(Json(json!({
+        "player_summary": summary.data,
+        "owned_games": games.data,
+        "meta": {
+            "player_summary": summary.meta(),
+            "owned_games": games.meta(),
+        }
+    })))
+}
+
+/// Player summaries for many profiles at once. Fresh cached summaries are
+/// used as-is and the rest are fetched a hundred per Steam call, so a friend
+/// list or leaderboard costs one unit of quota per hundred players.
+async fn get_player_summaries(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<SummariesRequest>,
+) -> Result<Json<Value>, ApiError> {
+    check_user_rate_limit(&state, addr.ip())?;
+
+    if request.steamids.len() > MAX_BATCH_IDS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} Steam IDs can be requested at once",
+            MAX_BATCH_IDS
+        )));
+    }
+
+    // Custom URLs would cost a ResolveVanityURL call each, so only ids are
+    // accepted here.
+    let mut steam_ids = Vec::new();
+    let mut seen = HashSet::new();
+    for raw in &request.steamids {
+        let id = match steam_id::parse(raw).map_err(|e| ApiError::InvalidSteamId(e.to_string()))? {
+            SteamIdInput::Id(id) => id.to_string(),
+            SteamIdInput::Vanity(name) => {
+                return Err(ApiError::InvalidSteamId(format!(
+                    "'{}' is a custom URL name; batch requests only take Steam IDs",
+                    name
+                )))
+            }
+        };
+        if seen.insert(id.clone()) {
+            steam_ids.push(id);
+        }
+    }
+
+    let mut batch = load_player_summaries(&state, addr.ip(), &steam_ids).await?;
+    // Only the caller's ids are at stake here, so a failed chunk fails it all
+    if let Some(e) = batch.error {
+        return Err(e);
+    }
+    let meta = batch_meta(&batch);
+
+    let mut players = Vec::new();
+    let mut not_found = Vec::new();
+    for id in steam_ids {
+        match batch.found.remove(&id) {
+            Some(snapshot) => players.push(snapshot.data),
+            None => not_found.push(id),
+        }
+    }
+
+    Ok(Json(json!({
+        "players": players,
+        "not_found": not_found,
+        "meta": meta,
+    })))
+}
+
+/// Where a batch of summaries came from.
+fn batch_meta(batch: &BatchSummaries) -> Value {
+    let found = batch.found.values();
+    let from_cache = found.clone().filter(|s| s.from_cache).count();
+    json!({
+        "from_cache": from_cache,
+        "from_steam": batch.found.len() - from_cache,
+        "stale": found.filter(|s| s.stale).count(),
+        "unavailable": batch.failed.len(),
+    })
+}
+
+/// The profile's friends, each with their player summary (name, avatar,
+/// online state). Summaries go through the batch path, so a whole friend
+/// list costs one Steam call per hundred uncached friends, charged to the
+/// client, and only the first [`MAX_BATCH_IDS`] friends get one. `summary`
+/// is null for friends Steam returned no profile for, past that limit, or
+/// whose summaries could not be fetched (counted in `meta.summaries`).
+async fn get_friends(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(raw_id): Path<String>,
+    Query(params): Query<RefreshParams>,
+) -> Result<Json<Value>, ApiError> {
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let steam_id = resolve_steam_id(&state, &raw_id).await?.to_string();
+    let friend_list = load_friend_list(&state, &steam_id, params.refresh).await?;
+
+    let friend_ids: Vec<String> = friend_list
+        .data
+        .friendslist
+        .friends
+        .iter()
+        .take(MAX_BATCH_IDS)
+        .map(|f| f.steamid.clone())
+        .collect();
+    // The friend list itself loaded, so failed summaries only blank theirs
+    let mut batch = load_player_summaries(&state, addr.ip(), &friend_ids).await?;
+    if let Some(e) = &batch.error {
+        eprintln!(
+            "Missing {} friend summaries for {}: {}",
+            batch.failed.len(),
+            steam_id,
+            e
+        );
+    }
+    let mut summaries_meta = batch_meta(&batch);
+    summaries_meta["skipped"] =
+        json!(friend_list.data.friendslist.friends.len() - friend_ids.len());
+
+    let friends: Vec<Value> = friend_list
+        .data
+        .friendslist
+        .friends
+        .iter()
+        .map(|friend| {
+            json!({
+                "steamid": friend.steamid,
+                "relationship": friend.relationship,
+                "friend_since": friend.friend_since,
+                "summary": batch.found.remove(&friend.steamid).map(|s| s.data),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "friends": friends,
+        "meta": {
+            "friend_list": friend_list.meta(),
+            "summaries": summaries_meta,
+        }
+    })))
+}
+
+/// Games played in the last two weeks, straight from Steam rather than
+/// inferred from `playtime_2weeks` in the owned games list.
+async fn get_recent_games(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(raw_id): Path<String>,
+    Query(params): Query<RefreshParams>,
+) -> Result<Json<Value>, ApiError> {
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let steam_id = resolve_steam_id(&state, &raw_id).await?.to_string();
+    let recent = load_recent_games(&state, &steam_id, params.refresh).await?;
+
+    Ok(Json(json!({
+        "recent_games": recent.data.response,
+        "meta": recent.meta(),
+    })))
+}
+
+/// Games every listed player owns, ranked by their combined playtime, for
+/// picking something to play together. Libraries come from the cached
+/// `owned_games` snapshots (fetched like `/user/:id` when missing or stale).
+/// Every blocking Steam call past the first is charged to the client before
+/// any is made, and the calls run concurrently.
+async fn get_common_games(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<CommonGamesParams>,
+) -> Result<Json<Value>, ApiError> {
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let raw_ids: Vec<&str> = params
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .collect();
+    if raw_ids.len() > MAX_COMMON_GAMES_PLAYERS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} players can be compared at once",
+            MAX_COMMON_GAMES_PLAYERS
+        )));
+    }
+
+    let inputs = raw_ids
+        .into_iter()
+        .map(steam_id::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::InvalidSteamId(e.to_string()))?;
+
+    // The request's own token pays for its first Steam call
+    let mut unresolved = HashSet::new();
+    for input in &inputs {
+        if let SteamIdInput::Vanity(name) = input {
+            if cached_vanity_url(&state, name).await.is_none() {
+                unresolved.insert(name.clone());
+            }
+        }
+    }
+    charge_user_rate_limit(&state, addr.ip(), unresolved.len().saturating_sub(1) as u32)?;
+
+    let resolved = join_all(inputs.into_iter().map(|input| resolve_input(&state, input))).await;
+    let mut steam_ids = Vec::new();
+    for id in resolved {
+        let id = id?.to_string();
+        if !steam_ids.contains(&id) {
+            steam_ids.push(id);
+        }
+    }
+    if steam_ids.len() < 2 {
+        return Err(ApiError::BadRequest(
+            "'ids' must list at least two different players".to_string(),
+        ));
+    }
+
+    let mut uncached = 0usize;
+    for steam_id in &steam_ids {
+        let cached =
+            snapshots::latest::<SteamResponse<OwnedGames>>(&state.db, steam_id, "owned_games")
+                .await
+                .filter(|c| visible_library(&c.data));
+        if cached.is_none() {
+            uncached += 1;
+        }
     }
+    let free = usize::from(unresolved.is_empty());
+    charge_user_rate_limit(&state, addr.ip(), uncached.saturating_sub(free) as u32)?;
 
-    // Check for cached summary
-    let cached_summary = sqlx::query(
-        "SELECT json_data, created_at FROM snapshots 
-         WHERE steam_id = ? AND data_type = 'player_summary' 
-         ORDER BY created_at DESC LIMIT 1",
+    let loaded = join_all(
+        steam_ids
+            .iter()
+            .map(|steam_id| load_owned_games(&state, steam_id, false)),
+    )
+    .await;
+    let mut libraries = Vec::new();
+    for (steam_id, games) in steam_ids.iter().zip(loaded) {
+        let games = match games {
+            // Say whose library is hidden
+            Err(ApiError::ProfilePrivate(message)) => {
+                return Err(ApiError::ProfilePrivate(format!(
+                    "{}: {}",
+                    steam_id, message
+                )))
+            }
+            result => result?,
+        };
+        libraries.push(games);
+    }
+
+    let named: Vec<(&str, &OwnedGames)> = steam_ids
+        .iter()
+        .map(String::as_str)
+        .zip(libraries.iter().map(|s| &s.data.response))
+        .collect();
+    let games = common_games::common_games(&named);
+
+    let meta: serde_json::Map<String, Value> = steam_ids
+        .iter()
+        .zip(&libraries)
+        .map(|(id, snapshot)| (id.clone(), snapshot.meta()))
+        .collect();
+
+    Ok(Json(json!({
+        "players": steam_ids,
+        "games": games,
+        "meta": { "owned_games": meta },
+    })))
+}
+
+/// Playtime per day/week computed from the stored `owned_games` snapshots.
+/// Only reads our own history; nothing is fetched from Steam.
+async fn get_playtime_history(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(raw_id): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Value>, ApiError> {
+    check_user_rate_limit(&state, addr.ip())?;
+
+    let to = match params.to.as_deref() {
+        Some(value) => parse_date("to", value)?,
+        None => Utc::now().date_naive(),
+    };
+    let from = match params.from.as_deref() {
+        Some(value) => parse_date("from", value)?,
+        None => to
+            .checked_sub_days(Days::new(30))
+            .ok_or_else(|| ApiError::BadRequest("'to' is out of range".to_string()))?,
+    };
+    if from > to {
+        return Err(ApiError::BadRequest(
+            "'from' must not be after 'to'".to_string(),
+        ));
+    }
+    let granularity = match params.bucket.as_deref() {
+        Some(value) => Granularity::parse(value).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "Unknown bucket '{}', expected 'daily' or 'weekly'",
+                value
+            ))
+        })?,
+        None => Granularity::Weekly,
+    };
+    // Every bucket is computed and returned, so the range must stay small.
+    let days = (to - from).num_days() as u64 + 1;
+    if days > granularity.max_range_days() {
+        return Err(ApiError::BadRequest(format!(
+            "The range covers {} days; at most {} are allowed with this bucket",
+            days,
+            granularity.max_range_days()
+        )));
+    }
+    let Some(after_to) = to.checked_add_days(Days::new(1)) else {
+        return Err(ApiError::BadRequest("'to' is out of range".to_string()));
+    };
+
+    let steam_id = resolve_steam_id(&state, &raw_id).await?.to_string();
+
+    // Weekly buckets may start before `from`; read their whole first week.
+    let range_start = granularity
+        .bucket_start(from)
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    let range_end = after_to.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let snapshots = snapshots::history::<SteamResponse<OwnedGames>>(
+        &state.db,
+        &steam_id,
+        "owned_games",
+        range_start,
+        range_end,
+    )
+    .await;
+
+    let history = history::playtime_history(&snapshots, from, to, granularity);
+    Ok(Json(json!(history)))
+}
+
+fn parse_date(name: &str, value: &str) -> Result<NaiveDate, ApiError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| ApiError::BadRequest(format!("'{}' must be a date like 2024-01-31", name)))
+}
+
+async fn load_player_summary(
+    state: &AppState,
+    steam_id: &str,
+    refresh: bool,
+) -> Result<Snapshot<SteamResponse<PlayerSummaries>>, ApiError> {
+    load_cached(
+        state,
+        steam_id,
+        "player_summary",
+        state.config.cache_ttl.player_summary,
+        refresh,
+        |c: &SteamResponse<PlayerSummaries>| !c.response.players.is_empty(),
+        |state, id| async move { fetch_and_store_player_summary(&state, &id).await },
     )
-    .bind(&steam_id)
-    .fetch_optional(&state.db)
     .await
-    .unwrap_or(None);
+}
+
+async fn fetch_and_store_player_summary(
+    state: &AppState,
+    steam_id: &str,
+) -> Result<Snapshot<SteamResponse<PlayerSummaries>>, ApiError> {
+    // Fetch from Steam
+    let data = state
+        .steam
+        .fetch_player_summaries(&[steam_id.to_string()])
+        .await?;
+
+    // A well-formed id that matches no account comes back as an empty list
+    let Some(p) = data.response.players.first() else {
+        return Err(ApiError::ProfileNotFound(format!(
+            "No Steam profile found for {}",
+            steam_id
+        )));
+    };
+
+    store_player_summary(state, p).await;
+
+    Ok(Snapshot::fresh(data))
+}
+
+/// Caches one player's summary (shaped like a single-id Steam response) and
+/// keeps the `users` table current.
+async fn store_player_summary(state: &AppState, player: &PlayerSummary) {
+    let data = SteamResponse {
+        response: PlayerSummaries {
+            players: vec![player.clone()],
+        },
+    };
+    snapshots::store(&state.db, &player.steamid, "player_summary", &data).await;
+
+    // Also upsert user table
+    let _ = sqlx::query(
+        "INSERT INTO users (steam_id, username, avatar_url) VALUES (?, ?, ?)
+         ON CONFLICT(steam_id) DO UPDATE SET username = ?, avatar_url = ?, last_updated = CURRENT_TIMESTAMP"
+    )
+    .bind(&player.steamid)
+    .bind(&player.personaname)
+    .bind(&player.avatarfull)
+    .bind(&player.personaname)
+    .bind(&player.avatarfull)
+    .execute(&state.db)
+    .await;
+}
 
-    let mut summary_data = None;
-    let mut use_cache = false;
-
-    if let Some(row) = cached_summary {
-        // Simple check: if exists, we can use it.
-        // TODO: Add robust 24h check with chrono.
-        // For now, assuming if it's there it's good enough for MVP, or we can just always refresh if user asks.
-        // Let's implement a basic "if older than 24h" logic later.
-        let json_str: String = row.get("json_data");
-        if let Ok(val) = serde_json::from_str::<Value>(&json_str) {
-            summary_data = Some(val);
-            use_cache = true;
+/// Summaries gathered by [`load_player_summaries`].
+struct BatchSummaries {
+    /// Keyed by SteamID64. Ids Steam doesn't know are missing.
+    found: HashMap<String, Snapshot<PlayerSummary>>,
+    /// Ids whose chunk failed, with no stale copy to stand in.
+    failed: Vec<String>,
+    /// Why the last failed chunk failed.
+    error: Option<ApiError>,
+}
+
+/// Summaries for many ids. Fresh cached ones are used as-is; the rest are
+/// fetched in chunks of [`MAX_SUMMARIES_PER_CALL`]. The request's own
+/// rate-limit token pays for the first chunk and `ip` is charged one more
+/// per further chunk. A failed chunk doesn't stop the others: if Steam is
+/// unavailable its stale copies stand in, and the remaining ids are reported
+/// in `failed`. Only a refused rate-limit charge is an error.
+async fn load_player_summaries(
+    state: &AppState,
+    ip: IpAddr,
+    steam_ids: &[String],
+) -> Result<BatchSummaries, ApiError> {
+    let mut summaries = HashMap::new();
+    let mut stale = HashMap::new();
+    let mut missing = Vec::new();
+    for id in steam_ids {
+        let cached =
+            snapshots::latest::<SteamResponse<PlayerSummaries>>(&state.db, id, "player_summary")
+                .await
+                .and_then(|cached| {
+                    let player = cached.data.response.players.first().cloned()?;
+                    Some(cached.map(|_| player))
+                });
+        match cached {
+            Some(cached) if cached.is_fresh(state.config.cache_ttl.player_summary) => {
+                summaries.insert(id.clone(), cached);
+            }
+            Some(cached) => {
+                stale.insert(id.clone(), cached);
+                missing.push(id.clone());
+            }
+            None => missing.push(id.clone()),
         }
     }
 
-    if !use_cache {
-        // Fetch from Steam
-        let api_key = env::var("STEAM_API_KEY").unwrap_or_default();
-        match steam_api::fetch_player_summary(
-            &state.client,
-            &api_key,
-            &steam_id,
-            &state.steam_global_limiter,
-        )
-        .await
-        {
-            Ok(data) => {
-                summary_data = Some(data.clone());
-                // Save to DB
-                let json_str = serde_json::to_string(&data).unwrap_or_default();
-                let _ = sqlx::query(
-                    "INSERT INTO snapshots (steam_id, data_type, json_data) VALUES (?, 'player_summary', ?)"
-                )
-                .bind(&steam_id)
-                .bind(json_str)
-                .execute(&state.db)
-                .await;
-
-                // Also upsert user table
-                let players = data["response"]["players"].as_array();
-                if let Some(players_arr) = players {
-                    if let Some(p) = players_arr.first() {
-                        let name = p["personaname"].as_str().unwrap_or("Unknown");
-                        let avatar = p["avatarfull"].as_str().unwrap_or("");
-                        let _ = sqlx::query(
-                             "INSERT INTO users (steam_id, username, avatar_url) VALUES (?, ?, ?)
-                              ON CONFLICT(steam_id) DO UPDATE SET username = ?, avatar_url = ?, last_updated = CURRENT_TIMESTAMP"
-                         )
-                         .bind(&steam_id)
-                         .bind(name)
-                         .bind(avatar)
-                         .bind(name)
-                         .bind(avatar)
-                         .execute(&state.db)
-                         .await;
-                    }
+    // Sorted so the same set of ids always makes the same chunks, which lets
+    // concurrent batches share them through single-flight.
+    missing.sort();
+    let chunks: Vec<&[String]> = missing.chunks(MAX_SUMMARIES_PER_CALL).collect();
+    charge_user_rate_limit(state, ip, chunks.len().saturating_sub(1) as u32)?;
+
+    let mut failed = Vec::new();
+    let mut error = None;
+    for chunk in chunks {
+        match refresh_player_summaries(state, chunk).await {
+            Ok(players) => {
+                for player in players {
+                    summaries.insert(player.steamid.clone(), Snapshot::fresh(player));
                 }
             }
             Err(e) => {
-                eprintln!("Failed to fetch summary: {}", e);
+                let outage = e.is_upstream_outage();
+                if outage {
+                    eprintln!("Serving stale summaries, Steam is unavailable: {}", e);
+                }
+                let already_failed = failed.len();
+                for id in chunk {
+                    match stale.remove(id).filter(|_| outage) {
+                        Some(mut cached) => {
+                            cached.stale = true;
+                            summaries.insert(id.clone(), cached);
+                        }
+                        None => failed.push(id.clone()),
+                    }
+                }
+                if failed.len() > already_failed {
+                    error = Some(e);
+                }
             }
         }
     }
 
-    // Fetch games (similar logic, kept simple here to do parallel fetch or sequential)
-    // For MVP, just fetching summary and returning it combined with games if possible.
+    Ok(BatchSummaries {
+        found: summaries,
+        failed,
+        error,
+    })
+}
+
+/// One GetPlayerSummaries call for up to [`MAX_SUMMARIES_PER_CALL`] ids,
+/// caching every player returned. Concurrent requests for the same chunk
+/// share a single upstream request.
+async fn refresh_player_summaries(
+    state: &AppState,
+    chunk: &[String],
+) -> Result<Vec<PlayerSummary>, ApiError> {
+    let key = (chunk.join(","), "player_summaries".to_string());
+    state
+        .in_flight
+        .run(key, || async {
+            let data = state.steam.fetch_player_summaries(chunk).await?;
+            for player in &data.response.players {
+                store_player_summary(state, player).await;
+            }
+            Ok(data.response.players)
+        })
+        .await
+}
 
-    // Let's fetch games too
-    let cached_games = sqlx::query(
-        "SELECT json_data FROM snapshots WHERE steam_id = ? AND data_type = 'owned_games' ORDER BY created_at DESC LIMIT 1"
+async fn load_owned_games(
+    state: &AppState,
+    steam_id: &str,
+    refresh: bool,
+) -> Result<Snapshot<SteamResponse<OwnedGames>>, ApiError> {
+    load_cached(
+        state,
+        steam_id,
+        "owned_games",
+        state.config.cache_ttl.owned_games,
+        refresh,
+        visible_library,
+        |state, id| async move { fetch_and_store_owned_games(&state, &id).await },
     )
-    .bind(&steam_id)
-    .fetch_optional(&state.db)
     .await
-    .unwrap_or(None);
+}
+
+/// Rows cached before private libraries were rejected are `{}`.
+fn visible_library(games: &SteamResponse<OwnedGames>) -> bool {
+    games.response.ensure_visible().is_ok()
+}
+
+async fn fetch_and_store_owned_games(
+    state: &AppState,
+    steam_id: &str,
+) -> Result<Snapshot<SteamResponse<OwnedGames>>, ApiError> {
+    let data = state.steam.fetch_owned_games(steam_id).await?;
+    // A private library comes back as an empty object; don't cache that
+    data.response.ensure_visible()?;
+
+    snapshots::store(&state.db, steam_id, "owned_games", &data).await;
+    if let Err(e) = ingest::owned_games(&state.db, steam_id, &data.response).await {
+        eprintln!("Failed to normalize owned games: {}", e);
+    }
+
+    Ok(Snapshot::fresh(data))
+}
+
+async fn load_recent_games(
+    state: &AppState,
+    steam_id: &str,
+    refresh: bool,
+) -> Result<Snapshot<SteamResponse<RecentlyPlayedGames>>, ApiError> {
+    load_cached(
+        state,
+        steam_id,
+        "recent_games",
+        state.config.cache_ttl.recent_games,
+        refresh,
+        |c: &SteamResponse<RecentlyPlayedGames>| c.response.ensure_visible().is_ok(),
+        |state, id| async move { fetch_and_store_recent_games(&state, &id).await },
+    )
+    .await
+}
+
+async fn fetch_and_store_recent_games(
+    state: &AppState,
+    steam_id: &str,
+) -> Result<Snapshot<SteamResponse<RecentlyPlayedGames>>, ApiError> {
+    let data = state.steam.fetch_recently_played_games(steam_id).await?;
+    data.response.ensure_visible()?;
+
+    snapshots::store(&state.db, steam_id, "recent_games", &data).await;
+
+    Ok(Snapshot::fresh(data))
+}
 
-    let mut games_data = None;
-    if let Some(row) = cached_games {
-        let json_str: String = row.get("json_data");
-        if let Ok(val) = serde_json::from_str::<Value>(&json_str) {
-            games_data = Some(val);
+async fn load_friend_list(
+    state: &AppState,
+    steam_id: &str,
+    refresh: bool,
+) -> Result<Snapshot<FriendListResponse>, ApiError> {
+    load_cached(
+        state,
+        steam_id,
+        "friend_list",
+        state.config.cache_ttl.friend_list,
+        refresh,
+        |_: &FriendListResponse| true,
+        |state, id| async move { fetch_and_store_friend_list(&state, &id).await },
+    )
+    .await
+}
+
+async fn fetch_and_store_friend_list(
+    state: &AppState,
+    steam_id: &str,
+) -> Result<Snapshot<FriendListResponse>, ApiError> {
+    let data = match state.steam.fetch_friend_list(steam_id).await {
+        // The generic message is about game details; this is a separate setting
+        Err(SteamApiError::PrivateProfile) => {
+            return Err(ApiError::ProfilePrivate(
+                "This Steam profile's friend list is private. In Steam, open Edit Profile > \
+                 Privacy Settings and set \"Friends List\" to Public, then try again."
+                    .to_string(),
+            ))
+        }
+        result => result?,
+    };
+
+    snapshots::store(&state.db, steam_id, "friend_list", &data).await;
+
+    Ok(Snapshot::fresh(data))
+}
+
+/// Stale-while-revalidate for one kind of per-profile Steam data: returns the
+/// latest cached snapshot straight away (refreshing it in the background once
+/// past `ttl`) and only blocks on Steam when nothing usable is cached or the
+/// caller forced a refresh. `fetch` gets the data from Steam and caches it.
+/// Rows `is_valid` rejects are treated as missing.
+async fn load_cached<T, F, Fut>(
+    state: &AppState,
+    steam_id: &str,
+    data_type: &str,
+    ttl: Duration,
+    refresh: bool,
+    is_valid: fn(&T) -> bool,
+    fetch: F,
+) -> Result<Snapshot<T>, ApiError>
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+    F: FnOnce(AppState, String) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Snapshot<T>, ApiError>> + Send + 'static,
+{
+    if !refresh {
+        let cached = snapshots::latest::<T>(&state.db, steam_id, data_type)
+            .await
+            .filter(|c| is_valid(&c.data));
+        if let Some(mut cached) = cached {
+            if !cached.is_fresh(ttl) {
+                cached.stale = true;
+                let key = data_type.to_string();
+                revalidate_in_background(state, steam_id, data_type, move |state, id| async move {
+                    refresh_cached(&state, &id, &key, fetch).await.map(drop)
+                });
+            }
+            return Ok(cached);
         }
     }
 
-    if games_data.is_none() {
-        let api_key = env::var("STEAM_API_KEY").unwrap_or_default();
-        if let Ok(data) = steam_api::fetch_owned_games(
-            &state.client,
-            &api_key,
-            &steam_id,
-            &state.steam_global_limiter,
-        )
+    match refresh_cached(state, steam_id, data_type, fetch).await {
+        Err(e) if e.is_upstream_outage() => {
+            fall_back_to_cache(state, steam_id, data_type, e, is_valid).await
+        }
+        result => result,
+    }
+}
+
+/// Runs `fetch`. Concurrent refreshes of the same profile and data type share
+/// a single upstream request.
+async fn refresh_cached<T, F, Fut>(
+    state: &AppState,
+    steam_id: &str,
+    data_type: &str,
+    fetch: F,
+) -> Result<Snapshot<T>, ApiError>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce(AppState, String) -> Fut,
+    Fut: Future<Output = Result<Snapshot<T>, ApiError>>,
+{
+    let key = (steam_id.to_string(), data_type.to_string());
+    state
+        .in_flight
+        .run(key, || fetch(state.clone(), steam_id.to_string()))
         .await
-        {
-            games_data = Some(data.clone());
-            let json_str = serde_json::to_string(&data).unwrap_or_default();
-            let _ = sqlx::query(
-                    "INSERT INTO snapshots (steam_id, data_type, json_data) VALUES (?, 'owned_games', ?)"
-                )
-                .bind(&steam_id)
-                .bind(json_str)
-                .execute(&state.db)
-                .await;
+}
+
+/// When Steam is down, hung or the circuit breaker is open, an old snapshot
+/// beats an error: serve the latest one flagged stale, or pass the error on.
+/// Rows `is_valid` rejects (error payloads cached before they were detected)
+/// are never served, not even as a fallback.
+async fn fall_back_to_cache<T: DeserializeOwned>(
+    state: &AppState,
+    steam_id: &str,
+    data_type: &str,
+    error: ApiError,
+    is_valid: fn(&T) -> bool,
+) -> Result<Snapshot<T>, ApiError> {
+    let cached = snapshots::latest::<T>(&state.db, steam_id, data_type)
+        .await
+        .filter(|c| is_valid(&c.data));
+    match cached {
+        Some(mut cached) => {
+            cached.stale = true;
+            Ok(cached)
         }
+        None => Err(error),
     }
+}
 
-    Json(json!({
-        "player_summary": summary_data,
-        "owned_games": games_data
-    }))
+/// Spawns `refresh` for a stale snapshot. Refreshes are single-flight, so a
+/// burst of stale hits still costs one upstream call, and the fetch waits its
+/// turn on the global Steam limiter like any other.
+fn revalidate_in_background<F, Fut>(state: &AppState, steam_id: &str, data_type: &str, refresh: F)
+where
+    F: FnOnce(AppState, String) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), ApiError>> + Send + 'static,
+{
+    // It would fail straight away; the cached copy is all we can offer.
+    if state.steam_breaker.is_open() {
+        return;
+    }
+
+    let state = state.clone();
+    let steam_id = steam_id.to_string();
+    let label = format!("{}:{}", steam_id, data_type);
+    tokio::spawn(async move {
+        if let Err(e) = refresh(state, steam_id).await {
+            eprintln!("Background refresh of {} failed: {}", label, e);
+        }
+    });
 }
@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+type Slot = Arc<OnceCell<Arc<dyn Any + Send + Sync>>>;
+
+/// Collapses concurrent calls for the same key into one execution: the first
+/// caller runs the future, everyone who arrives while it is in flight waits
+/// for and receives a clone of the same result. Once it completes the key is
+/// forgotten, so later calls run again.
+///
+/// Values are type-erased so one instance can serve every data type; a key
+/// must always be used with the same result type.
+pub struct SingleFlight<K> {
+    in_flight: Mutex<HashMap<K, Slot>>,
+}
+
+impl<K: Eq + Hash + Clone> SingleFlight<K> {
+    pub fn new() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<T, F, Fut>(&self, key: K, f: F) -> T
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let slot = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        // If the caller running `f` is cancelled, the next waiter takes over.
+        let value = slot
+            .get_or_init(|| async { Arc::new(f().await) as Arc<dyn Any + Send + Sync> })
+            .await
+            .clone();
+
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &slot))
+            {
+                in_flight.remove(&key);
+            }
+        }
+
+        value
+            .downcast_ref::<T>()
+            .expect("single-flight key reused with a different result type")
+            .clone()
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for SingleFlight<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
+use std::time::Duration;
+
+/// A Steam payload together with when we got it from Steam.
+#[derive(Debug, Clone)]
+pub struct Snapshot<T> {
+    pub data: T,
+    pub fetched_at: DateTime<Utc>,
+    pub from_cache: bool,
+    /// Served past its TTL while a refresh happens in the background.
+    pub stale: bool,
+}
+
+impl<T> Snapshot<T> {
+    pub fn fresh(data: T) -> Self {
+        Snapshot {
+            data,
+            fetched_at: Utc::now(),
+            from_cache: false,
+            stale: false,
+        }
+    }
+
+    /// The same snapshot with its payload transformed.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Snapshot<U> {
+        Snapshot {
+            data: f(self.data),
+            fetched_at: self.fetched_at,
+            from_cache: self.from_cache,
+            stale: self.stale,
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.fetched_at).to_std().unwrap_or_default()
+    }
+
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        self.age() < ttl
+    }
+
+    /// Response metadata telling the client where the data came from and how
+    /// old it is.
+    pub fn meta(&self) -> Value {
+        json!({
+            "source": if self.from_cache { "cache" } else { "steam" },
+            "fetched_at": self.fetched_at,
+            "age_seconds": self.age().as_secs(),
+            "stale": self.stale,
+        })
+    }
+}
+
+/// Latest snapshot of `data_type` for a profile. Rows that no longer parse as
+/// `T` are treated as missing so they get refetched.
+pub async fn latest<T: DeserializeOwned>(
+    db: &Pool<Sqlite>,
+    steam_id: &str,
+    data_type: &str,
+) -> Option<Snapshot<T>> {
+    // A deduplicated row was last confirmed by Steam at last_seen_at.
+    let row = sqlx::query(
+        "SELECT CAST(json_data AS BLOB) AS json_data, codec,
+                COALESCE(last_seen_at, created_at) AS fetched_at
+         FROM snapshots
+         WHERE steam_id = ? AND data_type = ?
+         ORDER BY created_at DESC, id DESC LIMIT 1",
+    )
+    .bind(steam_id)
+    .bind(data_type)
+    .fetch_optional(db)
+    .await
+    .unwrap_or(None)?;
+
+    decode_row(&row)
+}
+
+/// Every snapshot of `data_type` taken in `[from, to)`, oldest first. The last
+/// snapshot taken before `from` is included too, so callers computing deltas
+/// have a baseline for the first period. Each is dated by when its content was
+/// first seen; deduplicated repeats add no rows.
+pub async fn history<T: DeserializeOwned>(
+    db: &Pool<Sqlite>,
+    steam_id: &str,
+    data_type: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<Snapshot<T>> {
+    let from = sql_timestamp(from);
+    let to = sql_timestamp(to);
+    let rows = sqlx::query(
+        "SELECT CAST(json_data AS BLOB) AS json_data, codec, created_at AS fetched_at
+         FROM snapshots
+         WHERE steam_id = ? AND data_type = ? AND created_at < ?
+           AND created_at >= COALESCE(
+               (SELECT MAX(created_at) FROM snapshots
+                WHERE steam_id = ? AND data_type = ? AND created_at < ?),
+               ?)
+         ORDER BY created_at, id",
+    )
+    .bind(steam_id)
+    .bind(data_type)
+    .bind(&to)
+    .bind(steam_id)
+    .bind(data_type)
+    .bind(&from)
+    .bind(&from)
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+
+    rows.iter().filter_map(decode_row).collect()
+}
+
+fn decode_row<T: DeserializeOwned>(row: &SqliteRow) -> Option<Snapshot<T>> {
+    let stored: Vec<u8> = row.try_get("json_data").ok()?;
+    let codec: String = row.try_get("codec").ok()?;
+    let fetched_at: NaiveDateTime = row.try_get("fetched_at").ok()?;
+    let data = serde_json::from_slice(&decode_payload(&stored, &codec)?).ok()?;
+
+    Some(Snapshot {
+        data,
+        fetched_at: fetched_at.and_utc(),
+        from_cache: true,
+        stale: false,
+    })
+}
+
+/// Same text format as SQLite's `CURRENT_TIMESTAMP`, so comparisons with
+/// `created_at` work lexically.
+fn sql_timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Stores a fresh payload. If it is byte-identical to the latest snapshot of
+/// the same profile and data type, that row's `last_seen_at` is bumped instead
+/// of inserting a duplicate, so idle accounts don't grow the table.
+pub async fn store<T: Serialize>(db: &Pool<Sqlite>, steam_id: &str, data_type: &str, data: &T) {
+    // snapshots.steam_id references users, and the summary that creates the
+    // user row may not have been stored yet (or at all, for achievements).
+    let _ = sqlx::query("INSERT OR IGNORE INTO users (steam_id) VALUES (?)")
+        .bind(steam_id)
+        .execute(db)
+        .await;
+
+    let json_str = serde_json::to_string(data).unwrap_or_default();
+    let hash = content_hash(&json_str);
+
+    let latest: Option<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT id, content_hash FROM snapshots
+         WHERE steam_id = ? AND data_type = ?
+         ORDER BY created_at DESC, id DESC LIMIT 1",
+    )
+    .bind(steam_id)
+    .bind(data_type)
+    .fetch_optional(db)
+    .await
+    .unwrap_or(None);
+
+    if let Some((id, Some(latest_hash))) = latest {
+        if latest_hash == hash {
+            let _ =
+                sqlx::query("UPDATE snapshots SET last_seen_at = CURRENT_TIMESTAMP WHERE id = ?")
+                    .bind(id)
+                    .execute(db)
+                    .await;
+            return;
+        }
+    }
+
+    let _ = sqlx::query(
+        "INSERT INTO snapshots (steam_id, data_type, json_data, codec, content_hash, last_seen_at)
+         VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)",
+    )
+    .bind(steam_id)
+    .bind(data_type)
+    .bind(encode_payload(&json_str))
+    .bind(CODEC_ZSTD)
+    .bind(hash)
+    .execute(db)
+    .await;
+}
+
+/// Recompresses rows written before compression existed. Runs at startup in
+/// batches so a large table doesn't hold one giant transaction.
+pub async fn compress_legacy_rows(db: &Pool<Sqlite>) -> Result<u64, sqlx::Error> {
+    let mut converted = 0;
+    loop {
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, json_data FROM snapshots WHERE codec = ? LIMIT 200")
+                .bind(CODEC_JSON)
+                .fetch_all(db)
+                .await?;
+        if rows.is_empty() {
+            return Ok(converted);
+        }
+
+        let mut tx = db.begin().await?;
+        for (id, json_str) in rows {
+            sqlx::query("UPDATE snapshots SET json_data = ?, codec = ? WHERE id = ?")
+                .bind(encode_payload(&json_str))
+                .bind(CODEC_ZSTD)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            converted += 1;
+        }
+        tx.commit().await?;
+    }
+}
+
+/// Values of `snapshots.codec`: how `json_data` is stored.
+const CODEC_JSON: &str = "json";
+const CODEC_ZSTD: &str = "zstd";
+
+fn encode_payload(json: &str) -> Vec<u8> {
+    // Writing to a Vec can't fail
+    zstd::encode_all(json.as_bytes(), 3).expect("zstd compression failed")
+}
+
+fn decode_payload(stored: &[u8], codec: &str) -> Option<Vec<u8>> {
+    match codec {
+        CODEC_JSON => Some(stored.to_vec()),
+        CODEC_ZSTD => zstd::decode_all(stored).ok(),
+        _ => None,
+    }
+}
+
+/// Hex SHA-256 of the serialized payload.
+fn content_hash(json: &str) -> String {
+    format!("{:x}", Sha256::digest(json.as_bytes()))
+}
@@ -0,0 +1,222 @@
+use super::{
+    Friend, FriendList, FriendListResponse, OwnedGames, PlayerStats, PlayerStatsResponse,
+    PlayerSummaries, PlayerSummary, RecentlyPlayedGames, SteamApiError, SteamClient, SteamResponse,
+    VanityUrlResolution, MAX_SUMMARIES_PER_CALL,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// In-memory [`SteamClient`] for tests. Unknown ids get the same answers
+/// Steam gives: no players, an empty (private) library, a private friend
+/// list, "no stats".
+#[derive(Default)]
+pub struct FakeSteamClient {
+    data: Mutex<FakeData>,
+    /// Calls per method name, e.g. `fetch_owned_games`.
+    calls: Mutex<HashMap<&'static str, usize>>,
+}
+
+#[derive(Default)]
+struct FakeData {
+    vanity_urls: HashMap<String, String>,
+    players: HashMap<String, PlayerSummary>,
+    owned_games: HashMap<String, OwnedGames>,
+    recent_games: HashMap<String, RecentlyPlayedGames>,
+    friends: HashMap<String, Vec<Friend>>,
+    achievements: HashMap<(String, u32), PlayerStats>,
+    /// Answer every call with this status instead of data.
+    fail_with: Option<reqwest::StatusCode>,
+}
+
+impl FakeSteamClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_vanity_url(&self, vanity: &str, steam_id: &str) {
+        let mut data = self.data.lock().unwrap();
+        data.vanity_urls
+            .insert(vanity.to_ascii_lowercase(), steam_id.to_string());
+    }
+
+    pub fn add_player(&self, player: PlayerSummary) {
+        let mut data = self.data.lock().unwrap();
+        data.players.insert(player.steamid.clone(), player);
+    }
+
+    pub fn set_owned_games(&self, steam_id: &str, games: OwnedGames) {
+        let mut data = self.data.lock().unwrap();
+        data.owned_games.insert(steam_id.to_string(), games);
+    }
+
+    pub fn set_recent_games(&self, steam_id: &str, games: RecentlyPlayedGames) {
+        let mut data = self.data.lock().unwrap();
+        data.recent_games.insert(steam_id.to_string(), games);
+    }
+
+    pub fn set_friends(&self, steam_id: &str, friends: Vec<Friend>) {
+        let mut data = self.data.lock().unwrap();
+        data.friends.insert(steam_id.to_string(), friends);
+    }
+
+    pub fn set_achievements(&self, steam_id: &str, app_id: u32, stats: PlayerStats) {
+        let mut data = self.data.lock().unwrap();
+        data.achievements
+            .insert((steam_id.to_string(), app_id), stats);
+    }
+
+    pub fn fail_with(&self, status: Option<reqwest::StatusCode>) {
+        self.data.lock().unwrap().fail_with = status;
+    }
+
+    /// How many times `method` was called, including failed calls.
+    pub fn calls(&self, method: &str) -> usize {
+        self.calls.lock().unwrap().get(method).copied().unwrap_or(0)
+    }
+
+    pub fn total_calls(&self) -> usize {
+        self.calls.lock().unwrap().values().sum()
+    }
+
+    fn record(&self, method: &'static str) -> Result<(), SteamApiError> {
+        *self.calls.lock().unwrap().entry(method).or_default() += 1;
+        match self.data.lock().unwrap().fail_with {
+            Some(status) => Err(SteamApiError::Status {
+                status,
+                retry_after: None,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A public profile with placeholder details.
+pub fn player(steam_id: &str, name: &str) -> PlayerSummary {
+    PlayerSummary {
+        steamid: steam_id.to_string(),
+        personaname: name.to_string(),
+        profileurl: format!("https://steamcommunity.com/profiles/{}/", steam_id),
+        avatar: String::new(),
+        avatarmedium: String::new(),
+        avatarfull: String::new(),
+        communityvisibilitystate: 3,
+        personastate: 0,
+        profilestate: Some(1),
+        lastlogoff: None,
+        realname: None,
+        timecreated: None,
+        loccountrycode: None,
+        gameid: None,
+        gameextrainfo: None,
+    }
+}
+
+#[async_trait]
+impl SteamClient for FakeSteamClient {
+    async fn resolve_vanity_url(
+        &self,
+        vanity: &str,
+    ) -> Result<SteamResponse<VanityUrlResolution>, SteamApiError> {
+        self.record("resolve_vanity_url")?;
+        let data = self.data.lock().unwrap();
+        let response = match data.vanity_urls.get(&vanity.to_ascii_lowercase()) {
+            Some(steam_id) => VanityUrlResolution {
+                success: 1,
+                steamid: Some(steam_id.clone()),
+                message: None,
+            },
+            None => VanityUrlResolution {
+                success: 42,
+                steamid: None,
+                message: Some("No match".to_string()),
+            },
+        };
+        Ok(SteamResponse { response })
+    }
+
+    async fn fetch_player_summaries(
+        &self,
+        steam_ids: &[String],
+    ) -> Result<SteamResponse<PlayerSummaries>, SteamApiError> {
+        self.record("fetch_player_summaries")?;
+        let data = self.data.lock().unwrap();
+        let players = steam_ids
+            .iter()
+            .take(MAX_SUMMARIES_PER_CALL)
+            .filter_map(|id| data.players.get(id).cloned())
+            .collect();
+        Ok(SteamResponse {
+            response: PlayerSummaries { players },
+        })
+    }
+
+    async fn fetch_owned_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<OwnedGames>, SteamApiError> {
+        self.record("fetch_owned_games")?;
+        let data = self.data.lock().unwrap();
+        let response = data
+            .owned_games
+            .get(steam_id)
+            .cloned()
+            .unwrap_or(OwnedGames {
+                game_count: None,
+                games: None,
+            });
+        Ok(SteamResponse { response })
+    }
+
+    async fn fetch_friend_list(&self, steam_id: &str) -> Result<FriendListResponse, SteamApiError> {
+        self.record("fetch_friend_list")?;
+        let data = self.data.lock().unwrap();
+        // Steam can't tell us the friends of a private profile
+        let friends = data
+            .friends
+            .get(steam_id)
+            .cloned()
+            .ok_or(SteamApiError::PrivateProfile)?;
+        Ok(FriendListResponse {
+            friendslist: FriendList { friends },
+        })
+    }
+
+    async fn fetch_recently_played_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<RecentlyPlayedGames>, SteamApiError> {
+        self.record("fetch_recently_played_games")?;
+        let data = self.data.lock().unwrap();
+        let response = data
+            .recent_games
+            .get(steam_id)
+            .cloned()
+            .unwrap_or(RecentlyPlayedGames {
+                total_count: None,
+                games: None,
+            });
+        Ok(SteamResponse { response })
+    }
+
+    async fn fetch_player_achievements(
+        &self,
+        steam_id: &str,
+        app_id: u32,
+    ) -> Result<PlayerStatsResponse, SteamApiError> {
+        self.record("fetch_player_achievements")?;
+        let data = self.data.lock().unwrap();
+        let playerstats = data
+            .achievements
+            .get(&(steam_id.to_string(), app_id))
+            .cloned()
+            .unwrap_or_else(|| PlayerStats {
+                steam_id: None,
+                game_name: None,
+                achievements: None,
+                success: false,
+                error: Some("Requested app has no stats".to_string()),
+            });
+        Ok(PlayerStatsResponse { playerstats })
+    }
+}
@@ -0,0 +1,623 @@
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{Config, FixtureMode, RetryPolicy};
+use crate::{fixtures, redact};
+use async_trait::async_trait;
+use governor::{
+    clock::DefaultClock, middleware::NoOpMiddleware, state::InMemoryState, RateLimiter,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+pub mod fake;
+
+pub type SteamLimiter =
+    RateLimiter<governor::state::NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SteamResponse<T> {
+    pub response: T,
+}
+
+/// GetPlayerSummaries answers for at most this many ids per call and
+/// silently drops the rest.
+pub const MAX_SUMMARIES_PER_CALL: usize = 100;
+
+/// ISteamUser/GetPlayerSummaries. Ids that match no account are simply
+/// missing from `players`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSummaries {
+    #[serde(default)]
+    pub players: Vec<PlayerSummary>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSummary {
+    pub steamid: String,
+    pub personaname: String,
+    pub profileurl: String,
+    pub avatar: String,
+    pub avatarmedium: String,
+    pub avatarfull: String,
+    pub communityvisibilitystate: u8,
+    #[serde(default)]
+    pub personastate: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profilestate: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lastlogoff: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timecreated: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loccountrycode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gameid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gameextrainfo: Option<String>,
+}
+
+impl PlayerSummary {
+    /// `communityvisibilitystate` is 3 for public profiles; anything else
+    /// means we only get the name and avatar.
+    pub fn is_public(&self) -> bool {
+        self.communityvisibilitystate == 3
+    }
+}
+
+/// ISteamUser/ResolveVanityURL. `success` is 1 on a match and 42 when no
+/// profile uses that custom URL.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VanityUrlResolution {
+    pub success: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steamid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// ISteamUser/GetFriendList. Wrapped in `friendslist` rather than
+/// `response`; a private friend list is a bare 401 instead.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FriendListResponse {
+    #[serde(default)]
+    pub friendslist: FriendList,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FriendList {
+    #[serde(default)]
+    pub friends: Vec<Friend>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Friend {
+    pub steamid: String,
+    pub relationship: String,
+    /// Unix time the friendship started.
+    #[serde(default)]
+    pub friend_since: i64,
+}
+
+/// IPlayerService/GetOwnedGames
+///
+/// Both fields are missing entirely when the library is not visible to us.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OwnedGames {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub games: Option<Vec<OwnedGame>>,
+}
+
+impl OwnedGames {
+    /// Steam answers `{"response":{}}` rather than an error when the
+    /// library is private. An account with no games still has `game_count`.
+    pub fn ensure_visible(&self) -> Result<(), SteamApiError> {
+        if self.game_count.is_none() && self.games.is_none() {
+            return Err(SteamApiError::PrivateProfile);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OwnedGame {
+    pub appid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub playtime_forever: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playtime_2weeks: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub img_icon_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_community_visible_stats: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtime_last_played: Option<i64>,
+}
+
+/// IPlayerService/GetRecentlyPlayedGames: games played in the last two
+/// weeks, most recent first. As with [`OwnedGames`], both fields are missing
+/// when the profile's game details are private.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecentlyPlayedGames {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub games: Option<Vec<OwnedGame>>,
+}
+
+impl RecentlyPlayedGames {
+    /// Nothing played lately still comes with `total_count: 0`.
+    pub fn ensure_visible(&self) -> Result<(), SteamApiError> {
+        if self.total_count.is_none() && self.games.is_none() {
+            return Err(SteamApiError::PrivateProfile);
+        }
+        Ok(())
+    }
+}
+
+/// ISteamUserStats/GetPlayerAchievements. Unlike the other endpoints this
+/// one wraps its payload in `playerstats` rather than `response`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerStatsResponse {
+    pub playerstats: PlayerStats,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerStats {
+    #[serde(rename = "steamID", skip_serializing_if = "Option::is_none")]
+    pub steam_id: Option<String>,
+    #[serde(rename = "gameName", skip_serializing_if = "Option::is_none")]
+    pub game_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub achievements: Option<Vec<PlayerAchievement>>,
+    #[serde(default)]
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PlayerStats {
+    /// Failures come back as `success: false` plus a message, e.g.
+    /// "Profile is not public" or "Requested app has no stats".
+    pub fn ensure_success(&self) -> Result<(), SteamApiError> {
+        if self.success {
+            return Ok(());
+        }
+        let error = self
+            .error
+            .clone()
+            .unwrap_or_else(|| "Steam returned no achievement data".to_string());
+        if error.to_ascii_lowercase().contains("not public") {
+            Err(SteamApiError::PrivateProfile)
+        } else {
+            Err(SteamApiError::NoStats(error))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerAchievement {
+    pub apiname: String,
+    pub achieved: u8,
+    #[serde(default)]
+    pub unlocktime: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SteamApiError {
+    /// The request never produced a response (connect, TLS, body read, ...).
+    Http(reqwest::Error),
+    /// Steam answered with a non-success status and a body we could not decode.
+    /// `retry_after` is Steam's `Retry-After`, when it sent one.
+    Status {
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// Steam answered but the body did not match the expected shape.
+    Decode(serde_json::Error),
+    /// Replay mode has no recording of this request (or couldn't read it).
+    Fixture(String),
+    /// `STEAM_API_KEY` is not set, so nothing was sent.
+    MissingApiKey,
+    /// The profile (or its game details) is private, so Steam sent no data.
+    PrivateProfile,
+    /// Steam answered with an error payload instead of stats, e.g. for a
+    /// game without achievements.
+    NoStats(String),
+    /// The circuit breaker is open; nothing was sent. Holds the time until
+    /// the next attempt is allowed.
+    CircuitOpen(Duration),
+}
+
+impl fmt::Display for SteamApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SteamApiError::Http(e) => write!(f, "request to Steam failed: {}", e),
+            SteamApiError::Status { status, .. } => write!(f, "Steam returned {}", status),
+            SteamApiError::Decode(e) => write!(f, "unexpected Steam response: {}", e),
+            SteamApiError::Fixture(message) => write!(f, "{}", message),
+            SteamApiError::MissingApiKey => write!(f, "STEAM_API_KEY is not set"),
+            SteamApiError::PrivateProfile => write!(f, "Steam profile is private"),
+            SteamApiError::NoStats(message) => write!(f, "Steam has no stats: {}", message),
+            SteamApiError::CircuitOpen(retry_in) => {
+                write!(f, "Steam circuit is open, retrying in {:?}", retry_in)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SteamApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SteamApiError::Http(e) => Some(e),
+            SteamApiError::Status { .. }
+            | SteamApiError::Fixture(_)
+            | SteamApiError::MissingApiKey
+            | SteamApiError::PrivateProfile
+            | SteamApiError::NoStats(_)
+            | SteamApiError::CircuitOpen(_) => None,
+            SteamApiError::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl SteamApiError {
+    /// Whether this says Steam is down or throttling us, as opposed to Steam
+    /// answering in a way we didn't like.
+    pub fn is_outage(&self) -> bool {
+        match self {
+            SteamApiError::Http(_) => true,
+            SteamApiError::Status { status, .. } => is_transient(*status),
+            _ => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SteamApiError {
+    fn from(e: reqwest::Error) -> Self {
+        // The URL carries our API key
+        SteamApiError::Http(redact::reqwest_error(e))
+    }
+}
+
+impl From<serde_json::Error> for SteamApiError {
+    fn from(e: serde_json::Error) -> Self {
+        SteamApiError::Decode(e)
+    }
+}
+
+/// Sends a GET built by `request_maker`, retrying transient failures per
+/// `policy` (all our Steam calls are idempotent reads).
+async fn execute_with_retry<T, F>(
+    limiter: &SteamLimiter,
+    policy: &RetryPolicy,
+    fixture_mode: &FixtureMode,
+    request_maker: F,
+) -> Result<T, SteamApiError>
+where
+    T: DeserializeOwned,
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    if let FixtureMode::Replay(dir) = fixture_mode {
+        let url = request_maker().build()?.url().clone();
+        return match fixtures::load(dir, &url).await {
+            Ok(fixture) => {
+                let status = reqwest::StatusCode::from_u16(fixture.status)
+                    .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+                decode(status, &fixture.body_bytes())
+            }
+            Err(e) => {
+                let message = format!(
+                    "replay mode: no usable fixture for {} at {} ({})",
+                    fixtures::request_line(&url),
+                    fixtures::path_for(dir, &url).display(),
+                    e
+                );
+                eprintln!("❌ {}", message);
+                Err(SteamApiError::Fixture(message))
+            }
+        };
+    }
+
+    let mut retries = 0;
+
+    loop {
+        // Every attempt, retries included, spends Steam quota.
+        limiter.until_ready().await;
+
+        let res = match request_maker().send().await {
+            Ok(res) => res,
+            Err(e) if retries < policy.max_retries => {
+                let delay = backoff(policy, retries);
+                let e = redact::reqwest_error(e);
+                eprintln!(
+                    "Request to Steam failed ({}). Retrying in {:?}...",
+                    e, delay
+                );
+                sleep(delay).await;
+                retries += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let status = res.status();
+
+        if is_transient(status) {
+            let asked = retry_after(res.headers());
+            let delay = asked.unwrap_or_else(|| backoff(policy, retries));
+            // Rather fail now than hold the caller past `max_delay`
+            if retries >= policy.max_retries || delay > policy.max_delay {
+                return Err(SteamApiError::Status {
+                    status,
+                    retry_after: asked,
+                });
+            }
+            eprintln!("Steam returned {}. Retrying in {:?}...", status, delay);
+            sleep(delay).await;
+            retries += 1;
+            continue;
+        }
+
+        // Some endpoints (achievements) report failures as a JSON body with a
+        // 4xx status, so try the body first and only fall back to the status.
+        let url = res.url().clone();
+        let body = match res.bytes().await {
+            Ok(body) => body,
+            Err(e) if retries < policy.max_retries => {
+                let delay = backoff(policy, retries);
+                let e = redact::reqwest_error(e);
+                eprintln!(
+                    "Reading Steam response failed ({}). Retrying in {:?}...",
+                    e, delay
+                );
+                sleep(delay).await;
+                retries += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let FixtureMode::Record(dir) = fixture_mode {
+            if let Err(e) = fixtures::save(dir, &url, status.as_u16(), &body).await {
+                eprintln!("Failed to record Steam fixture: {}", e);
+            }
+        }
+        return decode(status, &body);
+    }
+}
+
+/// Throttling and gateway-style failures that usually clear up on their own.
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// `Retry-After` as delay-seconds or an HTTP date.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.signed_duration_since(chrono::Utc::now());
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Exponential backoff with "equal jitter": half the delay is fixed, the other
+/// half random, so retries from concurrent requests spread out.
+fn backoff(policy: &RetryPolicy, retries: u32) -> Duration {
+    let exponential = policy
+        .base_delay
+        .saturating_mul(2u32.saturating_pow(retries))
+        .min(policy.max_delay);
+    let half = exponential / 2;
+    half + half.mul_f64(rand::random::<f64>())
+}
+
+fn decode<T: DeserializeOwned>(
+    status: reqwest::StatusCode,
+    body: &[u8],
+) -> Result<T, SteamApiError> {
+    match serde_json::from_slice::<T>(body) {
+        Ok(data) => Ok(data),
+        Err(_) if !status.is_success() => Err(SteamApiError::Status {
+            status,
+            retry_after: None,
+        }),
+        Err(e) => Err(SteamApiError::Decode(e)),
+    }
+}
+
+/// The Steam Web API calls the backend makes. Handlers go through this
+/// trait (held in `AppState`) so the transport can be swapped: the real HTTP
+/// client in production, [`fake::FakeSteamClient`] in tests.
+#[async_trait]
+pub trait SteamClient: Send + Sync {
+    async fn resolve_vanity_url(
+        &self,
+        vanity: &str,
+    ) -> Result<SteamResponse<VanityUrlResolution>, SteamApiError>;
+
+    /// One call for up to [`MAX_SUMMARIES_PER_CALL`] ids.
+    async fn fetch_player_summaries(
+        &self,
+        steam_ids: &[String],
+    ) -> Result<SteamResponse<PlayerSummaries>, SteamApiError>;
+
+    async fn fetch_owned_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<OwnedGames>, SteamApiError>;
+
+    async fn fetch_friend_list(&self, steam_id: &str) -> Result<FriendListResponse, SteamApiError>;
+
+    async fn fetch_recently_played_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<RecentlyPlayedGames>, SteamApiError>;
+
+    async fn fetch_player_achievements(
+        &self,
+        steam_id: &str,
+        app_id: u32,
+    ) -> Result<PlayerStatsResponse, SteamApiError>;
+}
+
+/// Talks to the real Steam Web API (or whatever `STEAM_API_BASE_URL` points
+/// at), sharing the global limiter across every call.
+pub struct HttpSteamClient {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    limiter: Arc<SteamLimiter>,
+    breaker: Arc<CircuitBreaker>,
+    retry_policy: RetryPolicy,
+    timeout: Duration,
+    fixture_mode: FixtureMode,
+}
+
+impl HttpSteamClient {
+    pub fn new(
+        client: reqwest::Client,
+        config: &Config,
+        api_key: String,
+        limiter: Arc<SteamLimiter>,
+        breaker: Arc<CircuitBreaker>,
+    ) -> Self {
+        HttpSteamClient {
+            client,
+            base_url: config.upstreams.steam_api.clone(),
+            api_key,
+            limiter,
+            breaker,
+            retry_policy: config.steam_retry,
+            timeout: config.timeouts.steam_api,
+            fixture_mode: config.steam_fixtures.clone(),
+        }
+    }
+
+    /// Replay mode never sends the key, so it doesn't need one.
+    fn api_key(&self) -> Result<&str, SteamApiError> {
+        match (&self.fixture_mode, self.api_key.is_empty()) {
+            (FixtureMode::Replay(_), _) | (_, false) => Ok(&self.api_key),
+            (_, true) => Err(SteamApiError::MissingApiKey),
+        }
+    }
+
+    /// While the breaker is open this fails at once, without waiting on the
+    /// limiter or retrying.
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, SteamApiError> {
+        let permit = self.breaker.acquire().map_err(SteamApiError::CircuitOpen)?;
+        let result = execute_with_retry(
+            &self.limiter,
+            &self.retry_policy,
+            &self.fixture_mode,
+            || self.client.get(url).timeout(self.timeout),
+        )
+        .await;
+        match &result {
+            Err(e) if e.is_outage() => permit.failure(),
+            _ => permit.success(),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl SteamClient for HttpSteamClient {
+    async fn resolve_vanity_url(
+        &self,
+        vanity: &str,
+    ) -> Result<SteamResponse<VanityUrlResolution>, SteamApiError> {
+        let url = format!(
+            "{}/ISteamUser/ResolveVanityURL/v0001/?key={}&vanityurl={}",
+            self.base_url,
+            self.api_key()?,
+            vanity
+        );
+        self.get(&url).await
+    }
+
+    async fn fetch_player_summaries(
+        &self,
+        steam_ids: &[String],
+    ) -> Result<SteamResponse<PlayerSummaries>, SteamApiError> {
+        let url = format!(
+            "{}/ISteamUser/GetPlayerSummaries/v0002/?key={}&steamids={}",
+            self.base_url,
+            self.api_key()?,
+            steam_ids.join(",")
+        );
+        self.get(&url).await
+    }
+
+    async fn fetch_owned_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<OwnedGames>, SteamApiError> {
+        let url = format!(
+            "{}/IPlayerService/GetOwnedGames/v0001/?key={}&steamid={}&include_appinfo=1&include_played_free_games=1",
+            self.base_url,
+            self.api_key()?,
+            steam_id
+        );
+        self.get(&url).await
+    }
+
+    async fn fetch_friend_list(&self, steam_id: &str) -> Result<FriendListResponse, SteamApiError> {
+        let url = format!(
+            "{}/ISteamUser/GetFriendList/v0001/?key={}&steamid={}&relationship=friend",
+            self.base_url,
+            self.api_key()?,
+            steam_id
+        );
+        match self.get(&url).await {
+            Err(SteamApiError::Status {
+                status: reqwest::StatusCode::UNAUTHORIZED,
+                ..
+            }) => Err(SteamApiError::PrivateProfile),
+            result => result,
+        }
+    }
+
+    async fn fetch_recently_played_games(
+        &self,
+        steam_id: &str,
+    ) -> Result<SteamResponse<RecentlyPlayedGames>, SteamApiError> {
+        let url = format!(
+            "{}/IPlayerService/GetRecentlyPlayedGames/v0001/?key={}&steamid={}",
+            self.base_url,
+            self.api_key()?,
+            steam_id
+        );
+        self.get(&url).await
+    }
+
+    async fn fetch_player_achievements(
+        &self,
+        steam_id: &str,
+        app_id: u32,
+    ) -> Result<PlayerStatsResponse, SteamApiError> {
+        let url = format!(
+            "{}/ISteamUserStats/GetPlayerAchievements/v0001/?appid={}&key={}&steamid={}",
+            self.base_url,
+            app_id,
+            self.api_key()?,
+            steam_id
+        );
+        self.get(&url).await
+    }
+}
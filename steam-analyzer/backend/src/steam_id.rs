@@ -0,0 +1,268 @@
+use std::fmt;
+
+/// SteamID64 of account id 0 in the public universe for individual accounts.
+/// Every individual SteamID64 is this base plus the 32-bit account id.
+const INDIVIDUAL_BASE: u64 = 76561197960265728;
+
+/// A normalized SteamID64 for an individual account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SteamId(u64);
+
+impl SteamId {
+    pub fn from_account_id(account_id: u32) -> Self {
+        SteamId(INDIVIDUAL_BASE + account_id as u64)
+    }
+
+    pub fn from_steam64(id: u64) -> Option<Self> {
+        let account_id = id.checked_sub(INDIVIDUAL_BASE)?;
+        if account_id == 0 || account_id > u32::MAX as u64 {
+            return None;
+        }
+        Some(SteamId(id))
+    }
+}
+
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What a user typed in, once we know what kind of identifier it is.
+/// Vanity names still need a ResolveVanityURL round-trip to become a SteamId.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SteamIdInput {
+    Id(SteamId),
+    Vanity(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSteamId(pub String);
+
+impl fmt::Display for InvalidSteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not a valid Steam ID, profile URL or custom URL name",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidSteamId {}
+
+/// Accepts SteamID64 (`76561197960287930`), SteamID3 (`[U:1:22202]`),
+/// legacy IDs (`STEAM_0:0:11101`), profile URLs
+/// (`steamcommunity.com/profiles/<id64>` or `steamcommunity.com/id/<name>`)
+/// and bare vanity names.
+pub fn parse(input: &str) -> Result<SteamIdInput, InvalidSteamId> {
+    let invalid = || InvalidSteamId(input.to_string());
+    let trimmed = input.trim().trim_end_matches('/');
+
+    if let Some(path) = strip_community_host(trimmed) {
+        // Links copied from Steam often carry `?l=english` or a fragment.
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let path = path.trim_end_matches('/');
+        // Ignore sub-pages such as `/profiles/<id64>/games`.
+        let mut segments = path.splitn(3, '/');
+        let segment_pair = segments.next().zip(segments.next());
+        return match segment_pair {
+            Some(("profiles", rest)) => parse_id(rest).map(SteamIdInput::Id).ok_or_else(invalid),
+            Some(("id", rest)) if is_vanity_name(rest) => {
+                Ok(SteamIdInput::Vanity(rest.to_string()))
+            }
+            _ => Err(invalid()),
+        };
+    }
+
+    if let Some(id) = parse_id(trimmed) {
+        return Ok(SteamIdInput::Id(id));
+    }
+
+    // Anything that looks like one of the structured formats but failed to
+    // parse is a typo, not a vanity name.
+    let looks_structured = trimmed.starts_with('[')
+        || trimmed.to_ascii_uppercase().starts_with("STEAM_")
+        // 17+ digits is a SteamID64 attempt, e.g. a group's 1035... id
+        || (trimmed.len() >= 17 && trimmed.bytes().all(|b| b.is_ascii_digit()));
+    if !looks_structured && is_vanity_name(trimmed) {
+        return Ok(SteamIdInput::Vanity(trimmed.to_string()));
+    }
+
+    Err(invalid())
+}
+
+fn strip_community_host(input: &str) -> Option<&str> {
+    let without_scheme = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))
+        .unwrap_or(input);
+    let without_www = without_scheme
+        .strip_prefix("www.")
+        .unwrap_or(without_scheme);
+    without_www.strip_prefix("steamcommunity.com/")
+}
+
+fn parse_id(input: &str) -> Option<SteamId> {
+    if input.len() == 17 && input.bytes().all(|b| b.is_ascii_digit()) {
+        return SteamId::from_steam64(input.parse().ok()?);
+    }
+    parse_steam3(input).or_else(|| parse_legacy(input))
+}
+
+/// `[U:1:22202]`, brackets optional.
+fn parse_steam3(input: &str) -> Option<SteamId> {
+    let inner = input
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(input);
+    let mut parts = inner.split(':');
+    let (kind, universe, account) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || !kind.eq_ignore_ascii_case("U") || universe != "1" {
+        return None;
+    }
+    let account_id: u32 = account.parse().ok()?;
+    (account_id != 0).then(|| SteamId::from_account_id(account_id))
+}
+
+/// `STEAM_X:Y:Z` where the account id is `Z * 2 + Y`. `X` is 0 on old
+/// clients and 1 on newer ones; both mean the public universe.
+fn parse_legacy(input: &str) -> Option<SteamId> {
+    let rest = input
+        .get(..6)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("STEAM_"))
+        .map(|_| &input[6..])?;
+    let mut parts = rest.split(':');
+    let (universe, low_bit, high) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || !matches!(universe, "0" | "1") {
+        return None;
+    }
+    let low_bit: u32 = match low_bit {
+        "0" => 0,
+        "1" => 1,
+        _ => return None,
+    };
+    let high: u32 = high.parse().ok()?;
+    let account_id = high.checked_mul(2)?.checked_add(low_bit)?;
+    (account_id != 0).then(|| SteamId::from_account_id(account_id))
+}
+
+/// Steam custom URLs are 2-32 characters of letters, digits, `_` and `-`.
+fn is_vanity_name(input: &str) -> bool {
+    (2..=32).contains(&input.len())
+        && input
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GABEN: SteamIdInput = SteamIdInput::Id(SteamId(76561197960287930));
+
+    fn vanity(name: &str) -> SteamIdInput {
+        SteamIdInput::Vanity(name.to_string())
+    }
+
+    #[test]
+    fn accepts_steam64() {
+        assert_eq!(parse("76561197960287930"), Ok(GABEN));
+        assert_eq!(parse("  76561197960287930 "), Ok(GABEN));
+    }
+
+    #[test]
+    fn accepts_steam3_with_or_without_brackets() {
+        assert_eq!(parse("[U:1:22202]"), Ok(GABEN));
+        assert_eq!(parse("U:1:22202"), Ok(GABEN));
+        assert_eq!(parse("[u:1:22202]"), Ok(GABEN));
+    }
+
+    #[test]
+    fn accepts_legacy_ids_in_both_universes() {
+        assert_eq!(parse("STEAM_0:0:11101"), Ok(GABEN));
+        assert_eq!(parse("STEAM_1:0:11101"), Ok(GABEN));
+        assert_eq!(parse("steam_0:0:11101"), Ok(GABEN));
+        assert_eq!(
+            parse("STEAM_0:1:11101"),
+            Ok(SteamIdInput::Id(SteamId::from_account_id(22203)))
+        );
+    }
+
+    #[test]
+    fn accepts_profile_urls() {
+        for url in [
+            "https://steamcommunity.com/profiles/76561197960287930",
+            "http://www.steamcommunity.com/profiles/76561197960287930/",
+            "steamcommunity.com/profiles/76561197960287930/games",
+            "https://steamcommunity.com/profiles/76561197960287930/?l=english",
+            "https://steamcommunity.com/profiles/[U:1:22202]",
+        ] {
+            assert_eq!(parse(url), Ok(GABEN), "{}", url);
+        }
+    }
+
+    #[test]
+    fn accepts_custom_urls_and_vanity_names() {
+        assert_eq!(
+            parse("https://steamcommunity.com/id/gabelogannewell/"),
+            Ok(vanity("gabelogannewell"))
+        );
+        assert_eq!(
+            parse("https://steamcommunity.com/id/gabelogannewell?l=german#top"),
+            Ok(vanity("gabelogannewell"))
+        );
+        assert_eq!(parse("gabe_newell-1"), Ok(vanity("gabe_newell-1")));
+    }
+
+    #[test]
+    fn rejects_out_of_range_account_ids() {
+        for input in [
+            // Below the individual base, and the base itself (account 0)
+            "76561197960265727",
+            "76561197960265728",
+            // Account id past u32::MAX
+            "76561202255233024",
+            "[U:1:4294967296]",
+            "[U:1:0]",
+            "STEAM_0:1:2147483648",
+            "STEAM_0:0:0",
+        ] {
+            assert!(parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_non_individual_accounts() {
+        for input in [
+            // Group (clan) ids
+            "103582791429521412",
+            "[g:1:4]",
+            "[G:1:4]",
+            // Wrong universe
+            "[U:2:22202]",
+            "STEAM_2:0:11101",
+            "https://steamcommunity.com/groups/valve",
+        ] {
+            assert!(parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for input in [
+            "",
+            "   ",
+            "x",
+            "STEAM_0:5:1",
+            "[U:1:22202",
+            "https://steamcommunity.com/",
+            "https://steamcommunity.com/profiles/",
+            "https://steamcommunity.com/profiles/gabe",
+            "https://steamcommunity.com/id/a",
+            "has spaces",
+        ] {
+            assert!(parse(input).is_err(), "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,1044 @@
+//! Drives `api_router()` end to end against the mock upstream server.
+
+use axum::Router;
+use backend::config::{BreakerPolicy, Config, FixtureMode, RetryPolicy};
+use backend::db::{self, AppState};
+use backend::mock_upstream::{self, MockOptions, MockUpstream};
+use backend::routes::api_router;
+use backend::snapshots;
+use backend::steam_api::fake::{self, FakeSteamClient};
+use backend::steam_api::{Friend, OwnedGame, OwnedGames};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const STEAM_KEY: &str = "TESTSTEAMKEY0123456789ABCDEF";
+const GEMINI_KEY: &str = "TESTGEMINIKEY0123456789ABCDEF";
+
+struct TestApp {
+    base: String,
+    mock: Arc<MockUpstream>,
+    http: reqwest::Client,
+    db: SqlitePool,
+    _db_dir: TempDir,
+}
+
+impl TestApp {
+    async fn spawn(options: MockOptions) -> Self {
+        Self::spawn_with(options, |_| {}).await
+    }
+
+    async fn spawn_with(options: MockOptions, configure: impl FnOnce(&mut Config)) -> Self {
+        Self::build(options, configure, None).await
+    }
+
+    /// Steam calls go to `steam`; images and Gemini still hit the mock server.
+    async fn spawn_with_steam(steam: Arc<FakeSteamClient>) -> Self {
+        Self::build(MockOptions::default(), |_| {}, Some(steam)).await
+    }
+
+    async fn build(
+        options: MockOptions,
+        configure: impl FnOnce(&mut Config),
+        steam: Option<Arc<FakeSteamClient>>,
+    ) -> Self {
+        // Every test sets the same values, so racing writes are harmless.
+        std::env::set_var("STEAM_API_KEY", STEAM_KEY);
+        std::env::set_var("GEMINI_API_KEY", GEMINI_KEY);
+
+        let (mock_addr, mock) = mock_upstream::spawn(([127, 0, 0, 1], 0).into(), options)
+            .await
+            .unwrap();
+        let mock_url = format!("http://{}", mock_addr);
+
+        let mut config = Config::from_env();
+        config.upstreams.steam_api = mock_url.clone();
+        config.upstreams.steam_cdn = mock_url.clone();
+        config.upstreams.steam_media = mock_url.clone();
+        config.upstreams.gemini = mock_url;
+        config.steam_retry = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(5),
+        };
+        configure(&mut config);
+
+        let db_dir = tempfile::tempdir().unwrap();
+        let db_url = format!(
+            "sqlite:{}?mode=rwc",
+            db_dir.path().join("test.db").display()
+        );
+        let pool = db::connect(&db_url).await.unwrap();
+        let mut state = AppState::new(pool.clone(), Arc::new(config));
+        if let Some(steam) = steam {
+            state.steam = steam;
+        }
+
+        let app = Router::new().nest("/api", api_router()).with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        TestApp {
+            base: format!("http://{}/api", addr),
+            mock,
+            http: reqwest::Client::new(),
+            db: pool,
+            _db_dir: db_dir,
+        }
+    }
+
+    async fn get(&self, path: &str) -> (StatusCode, Value) {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base, path))
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, response.json().await.unwrap_or(Value::Null))
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> (StatusCode, Value) {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base, path))
+            .json(body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, response.json().await.unwrap_or(Value::Null))
+    }
+}
+
+#[tokio::test]
+async fn user_data_is_fetched_once_then_served_from_cache() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["player_summary"]["response"]["players"][0]["steamid"],
+        mock_upstream::STEAM_ID
+    );
+    assert_eq!(body["owned_games"]["response"]["game_count"], 3);
+    assert_eq!(body["meta"]["player_summary"]["source"], "steam");
+    assert_eq!(app.mock.total_hits(), 2);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["player_summary"]["source"], "cache");
+    assert_eq!(body["meta"]["owned_games"]["source"], "cache");
+    assert_eq!(app.mock.total_hits(), 2);
+
+    let (status, _) = app.get(&format!("{}?refresh=true", path)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.mock.total_hits(), 4);
+}
+
+#[tokio::test]
+async fn custom_urls_are_resolved_through_steam() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let (status, body) = app.get("/steam/user/mockuser").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["player_summary"]["response"]["players"][0]["steamid"],
+        mock_upstream::STEAM_ID
+    );
+    assert_eq!(app.mock.hits("/ISteamUser/ResolveVanityURL"), 1);
+
+    // The resolution is cached along with the profile data
+    let (status, _) = app.get("/steam/user/mockuser").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.mock.hits("/ISteamUser/ResolveVanityURL"), 1);
+
+    let (status, body) = app.get("/steam/user/nobody_here").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "profile_not_found");
+}
+
+#[tokio::test]
+async fn malformed_ids_never_reach_steam() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let (status, body) = app.get("/steam/user/STEAM_0:5:1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_steam_id");
+    assert_eq!(app.mock.total_hits(), 0);
+}
+
+#[tokio::test]
+async fn concurrent_requests_share_one_upstream_call() {
+    let app = TestApp::spawn(MockOptions {
+        latency: Duration::from_millis(200),
+        ..Default::default()
+    })
+    .await;
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+
+    let (a, b, c) = tokio::join!(app.get(&path), app.get(&path), app.get(&path));
+    assert_eq!(
+        (a.0, b.0, c.0),
+        (StatusCode::OK, StatusCode::OK, StatusCode::OK)
+    );
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 1);
+    assert_eq!(app.mock.hits("/IPlayerService/GetOwnedGames"), 1);
+}
+
+#[tokio::test]
+async fn achievements_are_cached_per_app() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!(
+        "/steam/user/{}/achievements/{}",
+        mock_upstream::STEAM_ID,
+        mock_upstream::ACHIEVEMENTS_APP_ID
+    );
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["playerstats"]["success"], true);
+    assert_eq!(body["meta"]["source"], "steam");
+
+    let (_, body) = app.get(&path).await;
+    assert_eq!(body["meta"]["source"], "cache");
+    assert_eq!(app.mock.hits("/ISteamUserStats"), 1);
+
+    let (status, body) = app
+        .get(&format!(
+            "/steam/user/{}/achievements/abc",
+            mock_upstream::STEAM_ID
+        ))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn steam_429s_are_retried() {
+    let app = TestApp::spawn(MockOptions {
+        rate_limit_every: Some(2),
+        ..Default::default()
+    })
+    .await;
+
+    let (status, _) = app
+        .get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.mock.total_hits(), 3);
+}
+
+#[tokio::test]
+async fn steam_outages_map_to_503() {
+    let app = TestApp::spawn(MockOptions {
+        fail_status: Some(500),
+        ..Default::default()
+    })
+    .await;
+
+    let (status, body) = app
+        .get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "steam_unavailable");
+    assert!(!body.to_string().contains(STEAM_KEY));
+
+    // The first attempt plus three retries
+    let (status, _) = app
+        .get(&format!(
+            "/steam/user/{}/achievements/{}",
+            mock_upstream::STEAM_ID,
+            mock_upstream::ACHIEVEMENTS_APP_ID
+        ))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(app.mock.hits("/ISteamUserStats"), 4);
+}
+
+#[tokio::test]
+async fn transient_steam_errors_are_retried() {
+    let app = TestApp::spawn(MockOptions {
+        fail_status: Some(502),
+        fail_first: Some(3),
+        ..Default::default()
+    })
+    .await;
+
+    let (status, _) = app
+        .get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.mock.total_hits(), 5);
+}
+
+#[tokio::test]
+async fn retry_after_is_honored() {
+    let app = TestApp::spawn(MockOptions {
+        rate_limit_every: Some(2),
+        ..Default::default()
+    })
+    .await;
+    let achievements = |app_id: u32| {
+        format!(
+            "/steam/user/{}/achievements/{}",
+            mock_upstream::STEAM_ID,
+            app_id
+        )
+    };
+
+    let (status, _) = app.get(&achievements(440)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The mock's 429 says `Retry-After: 1`, far above the test backoff.
+    let started = std::time::Instant::now();
+    let (status, _) = app.get(&achievements(620)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(app.mock.total_hits(), 3);
+}
+
+#[tokio::test]
+async fn steams_retry_after_is_passed_on() {
+    let app = TestApp::spawn_with(
+        MockOptions {
+            rate_limit_every: Some(1),
+            ..Default::default()
+        },
+        // Steam's `Retry-After: 1` is too long to wait out
+        |config| config.steam_retry.max_delay = Duration::from_millis(500),
+    )
+    .await;
+
+    let response = app
+        .http
+        .get(format!(
+            "{}/steam/user/{}",
+            app.base,
+            mock_upstream::STEAM_ID
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "1");
+}
+
+#[tokio::test]
+async fn unreachable_steam_is_retried_then_reported() {
+    let app = TestApp::spawn_with(MockOptions::default(), |config| {
+        // Nothing listens on port 1
+        config.upstreams.steam_api = "http://127.0.0.1:1".to_string();
+        config.steam_retry.max_retries = 1;
+    })
+    .await;
+
+    let (status, body) = app
+        .get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["message"], "Could not reach Steam");
+}
+
+#[tokio::test]
+async fn gemini_requests_are_proxied() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let response = app
+        .http
+        .post(format!("{}/ai/generate", app.base))
+        .json(&json!({ "prompt": "Summarize my library" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["candidates"][0]["content"]["parts"][0]["text"],
+        mock_upstream::GEMINI_TEXT
+    );
+    assert_eq!(app.mock.hits("/v1beta/models"), 1);
+}
+
+#[tokio::test]
+async fn images_fall_back_from_hero_to_header() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let response = app
+        .http
+        .get(format!("{}/images/banner/440", app.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+    assert_eq!(response.text().await.unwrap(), "mock image header.jpg");
+    assert_eq!(app.mock.hits("/store_item_assets"), 2);
+}
+
+#[tokio::test]
+async fn recorded_responses_replay_without_steam() {
+    let fixtures = tempfile::tempdir().unwrap();
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+
+    let recorder = TestApp::spawn_with(MockOptions::default(), |config| {
+        config.steam_fixtures = FixtureMode::Record(fixtures.path().to_path_buf());
+    })
+    .await;
+    let (status, recorded) = recorder.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for entry in std::fs::read_dir(fixtures.path()).unwrap() {
+        let contents = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(!contents.contains(STEAM_KEY));
+    }
+
+    // Steam being down proves nothing is fetched live.
+    let replayer = TestApp::spawn_with(
+        MockOptions {
+            fail_status: Some(500),
+            ..Default::default()
+        },
+        |config| config.steam_fixtures = FixtureMode::Replay(fixtures.path().to_path_buf()),
+    )
+    .await;
+    let (status, replayed) = replayer.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replayed["player_summary"], recorded["player_summary"]);
+    assert_eq!(replayed["owned_games"], recorded["owned_games"]);
+    assert_eq!(replayer.mock.total_hits(), 0);
+
+    let (status, body) = replayer
+        .get(&format!(
+            "/steam/user/{}/achievements/{}",
+            mock_upstream::STEAM_ID,
+            mock_upstream::ACHIEVEMENTS_APP_ID
+        ))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "fixture_missing");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("no usable fixture"));
+}
+
+#[tokio::test]
+async fn replay_misses_are_not_hidden_by_the_cache() {
+    let fixtures = tempfile::tempdir().unwrap();
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+
+    let recorder = TestApp::spawn_with(MockOptions::default(), |config| {
+        config.steam_fixtures = FixtureMode::Record(fixtures.path().to_path_buf());
+    })
+    .await;
+    let (status, _) = recorder.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let replayer = TestApp::spawn_with(MockOptions::default(), |config| {
+        config.steam_fixtures = FixtureMode::Replay(fixtures.path().to_path_buf())
+    })
+    .await;
+    // Seeds the replayer's cache from the recordings
+    let (status, _) = replayer.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for entry in std::fs::read_dir(fixtures.path()).unwrap() {
+        std::fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+    let (status, body) = replayer.get(&format!("{}?refresh=true", path)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "fixture_missing");
+    assert_eq!(replayer.mock.total_hits(), 0);
+}
+
+fn owned_game(appid: u32, name: &str, playtime_forever: u32) -> OwnedGame {
+    OwnedGame {
+        appid,
+        name: Some(name.to_string()),
+        playtime_forever,
+        playtime_2weeks: None,
+        img_icon_url: None,
+        has_community_visible_stats: None,
+        rtime_last_played: None,
+    }
+}
+
+#[tokio::test]
+async fn handlers_run_against_a_fake_steam_client() {
+    let steam = Arc::new(FakeSteamClient::new());
+    let steam_id = "76561197960265729";
+    steam.add_player(fake::player(steam_id, "Gabe"));
+    steam.add_vanity_url("gabe", steam_id);
+    steam.set_owned_games(
+        steam_id,
+        OwnedGames {
+            game_count: Some(1),
+            games: Some(vec![owned_game(10, "Counter-Strike", 600)]),
+        },
+    );
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+
+    let (status, body) = app.get("/steam/user/gabe").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["player_summary"]["response"]["players"][0]["personaname"],
+        "Gabe"
+    );
+    assert_eq!(
+        body["owned_games"]["response"]["games"][0]["name"],
+        "Counter-Strike"
+    );
+    assert_eq!(steam.calls("resolve_vanity_url"), 1);
+    assert_eq!(steam.calls("fetch_player_summaries"), 1);
+    assert_eq!(app.mock.total_hits(), 0);
+
+    let (status, body) = app.get("/steam/user/76561197960265730").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "profile_not_found");
+}
+
+#[tokio::test]
+async fn fake_steam_errors_map_like_real_ones() {
+    let steam = Arc::new(FakeSteamClient::new());
+    steam.fail_with(Some(StatusCode::TOO_MANY_REQUESTS));
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+
+    let response = app
+        .http
+        .get(format!("{}/steam/user/76561197960265729", app.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "60");
+}
+
+#[tokio::test]
+async fn open_circuit_serves_stale_cache_and_recovers() {
+    let app = TestApp::spawn_with(MockOptions::default(), |config| {
+        config.steam_retry.max_retries = 0;
+        config.steam_breaker = BreakerPolicy {
+            failure_threshold: 2,
+            open_for: Duration::from_millis(500),
+        };
+    })
+    .await;
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Both calls fail, which trips the breaker; the cache covers for Steam.
+    app.mock.set_options(MockOptions {
+        fail_status: Some(503),
+        ..Default::default()
+    });
+    let refresh = format!("{}?refresh=true", path);
+    let (status, body) = app.get(&refresh).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["player_summary"]["stale"], true);
+    assert_eq!(body["meta"]["owned_games"]["stale"], true);
+
+    let (_, health) = app.get("/health").await;
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(health["steam"]["circuit"]["state"], "open");
+
+    // While open nothing reaches Steam.
+    let hits = app.mock.total_hits();
+    let (status, _) = app.get(&refresh).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app
+        .get(&format!(
+            "/steam/user/{}/achievements/{}",
+            mock_upstream::STEAM_ID,
+            mock_upstream::ACHIEVEMENTS_APP_ID
+        ))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "steam_unavailable");
+    assert_eq!(app.mock.total_hits(), hits);
+
+    // After the open period one probe goes through and closes the circuit.
+    app.mock.set_options(MockOptions::default());
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let (status, _) = app.get(&refresh).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, health) = app.get("/health").await;
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["steam"]["circuit"]["state"], "closed");
+
+    let (_, body) = app.get(&refresh).await;
+    assert_eq!(body["meta"]["player_summary"]["source"], "steam");
+    assert_eq!(body["meta"]["owned_games"]["source"], "steam");
+}
+
+#[tokio::test]
+async fn hung_upstreams_time_out_with_504() {
+    let app = TestApp::spawn_with(
+        MockOptions {
+            latency: Duration::from_secs(2),
+            ..Default::default()
+        },
+        |config| {
+            config.steam_retry.max_retries = 0;
+            config.timeouts.steam_api = Duration::from_millis(100);
+            config.timeouts.cdn = Duration::from_millis(100);
+            config.timeouts.gemini = Duration::from_millis(100);
+        },
+    )
+    .await;
+
+    let (status, body) = app
+        .get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+
+    let (status, body) = app.get("/images/banner/440").await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+
+    let response = app
+        .http
+        .post(format!("{}/ai/generate", app.base))
+        .json(&json!({ "prompt": "Hello" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn private_profiles_are_reported_and_not_cached() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!("/steam/user/{}", mock_upstream::PRIVATE_STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "profile_private");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Privacy Settings"));
+
+    // Nothing was cached as a success, so Steam is asked again
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(app.mock.hits("/IPlayerService/GetOwnedGames"), 2);
+
+    let achievements = format!(
+        "/steam/user/{}/achievements/{}",
+        mock_upstream::PRIVATE_STEAM_ID,
+        mock_upstream::ACHIEVEMENTS_APP_ID
+    );
+    let (status, body) = app.get(&achievements).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "profile_private");
+    app.get(&achievements).await;
+    assert_eq!(app.mock.hits("/ISteamUserStats"), 2);
+}
+
+#[tokio::test]
+async fn games_without_stats_are_not_cached() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!("/steam/user/{}/achievements/620", mock_upstream::STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "no_stats");
+    app.get(&path).await;
+    assert_eq!(app.mock.hits("/ISteamUserStats"), 2);
+}
+
+#[tokio::test]
+async fn batch_summaries_fetch_only_uncached_ids_a_hundred_at_a_time() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    app.get(&format!("/steam/user/{}", mock_upstream::STEAM_ID))
+        .await;
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 1);
+
+    let mut ids = vec![mock_upstream::STEAM_ID.to_string()];
+    ids.extend((0..149u64).map(|i| (76561198000000000 + i).to_string()));
+    let request = json!({ "steamids": ids });
+
+    let (status, body) = app.post("/steam/users/summaries", &request).await;
+    assert_eq!(status, StatusCode::OK);
+    let players = body["players"].as_array().unwrap();
+    assert_eq!(players.len(), 150);
+    assert_eq!(players[0]["steamid"], mock_upstream::STEAM_ID);
+    assert_eq!(players[149]["steamid"], "76561198000000148");
+    assert_eq!(body["meta"]["from_cache"], 1);
+    assert_eq!(body["meta"]["from_steam"], 149);
+    // 149 uncached ids: one call for 100, one for 49
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 3);
+
+    let (status, body) = app.post("/steam/users/summaries", &request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["from_cache"], 150);
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 3);
+}
+
+#[tokio::test]
+async fn batch_summaries_only_take_steam_ids() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let request = json!({ "steamids": [mock_upstream::STEAM_ID, "mockuser"] });
+    let (status, body) = app.post("/steam/users/summaries", &request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_steam_id");
+    assert_eq!(app.mock.total_hits(), 0);
+}
+
+#[tokio::test]
+async fn batch_summaries_report_unknown_ids() {
+    let steam = Arc::new(FakeSteamClient::new());
+    steam.add_player(fake::player("76561197960287930", "Known"));
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+
+    // Duplicates and other id formats collapse to one SteamID64
+    let request = json!({
+        "steamids": ["76561197960287930", "[U:1:22202]", "76561197960265731"]
+    });
+    let (status, body) = app.post("/steam/users/summaries", &request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["players"].as_array().unwrap().len(), 1);
+    assert_eq!(body["players"][0]["personaname"], "Known");
+    assert_eq!(body["not_found"], json!(["76561197960265731"]));
+    assert_eq!(steam.calls("fetch_player_summaries"), 1);
+}
+
+#[tokio::test]
+async fn recent_games_are_cached() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!("/steam/user/{}/recent", mock_upstream::STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["recent_games"]["total_count"], 1);
+    assert_eq!(body["recent_games"]["games"][0]["appid"], 440);
+    assert_eq!(body["meta"]["source"], "steam");
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["source"], "cache");
+    assert_eq!(app.mock.hits("/IPlayerService/GetRecentlyPlayedGames"), 1);
+
+    let path = format!("/steam/user/{}/recent", mock_upstream::PRIVATE_STEAM_ID);
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "profile_private");
+}
+
+#[tokio::test]
+async fn outages_dont_fall_back_to_cached_error_payloads() {
+    let steam = Arc::new(FakeSteamClient::new());
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+    let steam_id = "76561197960265729";
+    // How older versions cached a private library
+    sqlx::query("INSERT INTO users (steam_id, username) VALUES (?, 'x')")
+        .bind(steam_id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+    snapshots::store(&app.db, steam_id, "recent_games", &json!({"response": {}})).await;
+
+    steam.fail_with(Some(StatusCode::SERVICE_UNAVAILABLE));
+    let (status, body) = app.get(&format!("/steam/user/{}/recent", steam_id)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "steam_unavailable");
+}
+
+#[tokio::test]
+async fn friends_come_with_batched_summaries() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    // One friend's summary is already cached
+    app.get("/steam/user/76561197960265729").await;
+    let path = format!("/steam/user/{}/friends", mock_upstream::STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    let friends = body["friends"].as_array().unwrap();
+    assert_eq!(friends.len(), 3);
+    assert_eq!(friends[0]["steamid"], "76561197960265729");
+    assert_eq!(friends[0]["friend_since"], 1500000000);
+    assert_eq!(friends[0]["summary"]["personaname"], "Mock Player 5729");
+    assert_eq!(friends[2]["summary"]["communityvisibilitystate"], 1);
+    assert_eq!(body["meta"]["summaries"]["from_cache"], 1);
+    assert_eq!(body["meta"]["summaries"]["from_steam"], 2);
+    // The two uncached friends share one call
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 2);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["friend_list"]["source"], "cache");
+    assert_eq!(body["meta"]["summaries"]["from_cache"], 3);
+    assert_eq!(app.mock.hits("/ISteamUser/GetFriendList"), 1);
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 2);
+}
+
+#[tokio::test]
+async fn friends_load_even_when_their_summaries_fail() {
+    let steam = Arc::new(FakeSteamClient::new());
+    let (me, friend) = ("76561197960287930", "76561197960265729");
+    steam.set_friends(
+        me,
+        vec![Friend {
+            steamid: friend.to_string(),
+            relationship: "friend".to_string(),
+            friend_since: 1500000000,
+        }],
+    );
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+    let path = format!("/steam/user/{}/friends", me);
+    // Caches the friend list; Steam knows no summary yet
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    steam.add_player(fake::player(friend, "Bob"));
+    steam.fail_with(Some(StatusCode::INTERNAL_SERVER_ERROR));
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["friends"][0]["steamid"], friend);
+    assert!(body["friends"][0]["summary"].is_null());
+    assert_eq!(body["meta"]["friend_list"]["source"], "cache");
+    assert_eq!(body["meta"]["summaries"]["unavailable"], 1);
+}
+
+#[tokio::test]
+async fn private_friend_lists_are_reported() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let path = format!("/steam/user/{}/friends", mock_upstream::PRIVATE_STEAM_ID);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "profile_private");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Friends List"));
+    // A 401 is an answer, not an outage: no retries
+    assert_eq!(app.mock.hits("/ISteamUser/GetFriendList"), 1);
+}
+
+#[tokio::test]
+async fn common_games_are_ranked_by_combined_playtime() {
+    let steam = Arc::new(FakeSteamClient::new());
+    let (alice, bob) = ("76561197960287930", "76561197960265729");
+    steam.add_vanity_url("bob", bob);
+    steam.set_owned_games(
+        alice,
+        OwnedGames {
+            game_count: Some(3),
+            games: Some(vec![
+                owned_game(440, "Team Fortress 2", 100),
+                owned_game(570, "Dota 2", 5000),
+                owned_game(620, "Portal 2", 300),
+            ]),
+        },
+    );
+    steam.set_owned_games(
+        bob,
+        OwnedGames {
+            game_count: Some(3),
+            games: Some(vec![
+                owned_game(620, "Portal 2", 50),
+                owned_game(440, "Team Fortress 2", 900),
+                owned_game(730, "Counter-Strike 2", 4000),
+            ]),
+        },
+    );
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+    let path = format!("/steam/common-games?ids={},bob", alice);
+
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["players"], json!([alice, bob]));
+    let games = body["games"].as_array().unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0]["appid"], 440);
+    assert_eq!(games[0]["combined_minutes"], 1000);
+    assert_eq!(games[0]["players"][1]["minutes"], 900);
+    assert_eq!(games[1]["appid"], 620);
+
+    // Libraries are cached like the profile endpoint's
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(steam.calls("fetch_owned_games"), 2);
+}
+
+#[tokio::test]
+async fn common_games_load_libraries_concurrently_and_charge_for_each() {
+    let app = TestApp::spawn(MockOptions {
+        latency: Duration::from_millis(200),
+        ..Default::default()
+    })
+    .await;
+    let path = |first: u64| {
+        let ids: Vec<String> = (first..first + 10)
+            .map(|i| (76561198000000000 + i).to_string())
+            .collect();
+        format!("/steam/common-games?ids={}", ids.join(","))
+    };
+
+    let started = std::time::Instant::now();
+    let (status, body) = app.get(&path(0)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["games"].as_array().unwrap().len(), 3);
+    // Ten libraries in about the time of one
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(app.mock.hits("/IPlayerService/GetOwnedGames"), 10);
+
+    // Ten calls per request: ten of the client's 30 tokens per minute
+    for first in [10, 20] {
+        let (status, _) = app.get(&path(first)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, body) = app.get(&path(30)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"]["code"], "rate_limited");
+    // Refused before anything was fetched
+    assert_eq!(app.mock.hits("/IPlayerService/GetOwnedGames"), 30);
+}
+
+#[tokio::test]
+async fn common_games_need_two_visible_libraries() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+
+    let path = format!(
+        "/steam/common-games?ids={},{}",
+        mock_upstream::STEAM_ID,
+        mock_upstream::STEAM_ID
+    );
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let path = format!(
+        "/steam/common-games?ids={},{}",
+        mock_upstream::STEAM_ID,
+        mock_upstream::PRIVATE_STEAM_ID
+    );
+    let (status, body) = app.get(&path).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with(mock_upstream::PRIVATE_STEAM_ID));
+}
+
+#[tokio::test]
+async fn history_ranges_are_bounded() {
+    let app = TestApp::spawn(MockOptions::default()).await;
+    let base = format!("/steam/user/{}/history", mock_upstream::STEAM_ID);
+
+    let (status, body) = app
+        .get(&format!(
+            "{}?from=0001-01-01&to=2024-01-01&bucket=daily",
+            base
+        ))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let (status, _) = app
+        .get(&format!(
+            "{}?from=2023-01-01&to=2024-01-01&bucket=daily",
+            base
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app
+        .get(&format!(
+            "{}?from=2022-01-01&to=2024-01-01&bucket=weekly",
+            base
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["buckets"].as_array().unwrap().len(), 106);
+    assert_eq!(app.mock.total_hits(), 0);
+}
+
+#[tokio::test]
+async fn hung_steam_falls_back_to_cached_snapshots() {
+    let app = TestApp::spawn_with(MockOptions::default(), |config| {
+        config.steam_retry.max_retries = 0;
+        config.timeouts.steam_api = Duration::from_millis(100);
+    })
+    .await;
+    let path = format!("/steam/user/{}", mock_upstream::STEAM_ID);
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::OK);
+
+    app.mock.set_options(MockOptions {
+        latency: Duration::from_secs(2),
+        ..Default::default()
+    });
+    let (status, body) = app.get(&format!("{}?refresh=true", path)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["meta"]["player_summary"]["source"], "cache");
+    assert_eq!(body["meta"]["player_summary"]["stale"], true);
+    assert_eq!(body["meta"]["owned_games"]["stale"], true);
+}
+
+#[tokio::test]
+async fn batch_summaries_charge_the_client_per_steam_call() {
+    let steam = Arc::new(FakeSteamClient::new());
+    let app = TestApp::spawn_with_steam(steam.clone()).await;
+
+    // Each batch of 1000 uncached ids is ten calls: ten of the client's 30
+    // tokens per minute
+    for batch in 0..3u64 {
+        let ids: Vec<String> = (0..1000u64)
+            .map(|i| (76561198000000000 + batch * 1000 + i).to_string())
+            .collect();
+        let (status, _) = app
+            .post("/steam/users/summaries", &json!({ "steamids": ids }))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(steam.calls("fetch_player_summaries"), 30);
+
+    let request = json!({ "steamids": [mock_upstream::STEAM_ID] });
+    let (status, body) = app.post("/steam/users/summaries", &request).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"]["code"], "rate_limited");
+    assert_eq!(steam.calls("fetch_player_summaries"), 30);
+}
+
+#[tokio::test]
+async fn concurrent_batches_share_steam_calls() {
+    let app = TestApp::spawn(MockOptions {
+        latency: Duration::from_millis(200),
+        ..Default::default()
+    })
+    .await;
+    let ids: Vec<String> = (0..150u64)
+        .map(|i| (76561198000000000 + i).to_string())
+        .collect();
+    let reversed: Vec<String> = ids.iter().rev().cloned().collect();
+    // Same ids in a different order still make the same chunks
+    let (first, second) = (json!({ "steamids": ids }), json!({ "steamids": reversed }));
+
+    let (first, second) = tokio::join!(
+        app.post("/steam/users/summaries", &first),
+        app.post("/steam/users/summaries", &second),
+    );
+    assert_eq!(first.0, StatusCode::OK);
+    assert_eq!(second.0, StatusCode::OK);
+    assert_eq!(second.1["players"].as_array().unwrap().len(), 150);
+    assert_eq!(app.mock.hits("/ISteamUser/GetPlayerSummaries"), 2);
+}
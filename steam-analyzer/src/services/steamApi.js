@@ -4,7 +4,14 @@ const BACKEND_URL = '/api';
 const fetchBackend = async (endpoint, options = {}) => {
     try {
         const res = await fetch(`${BACKEND_URL}${endpoint}`, options);
-        if (!res.ok) throw new Error(`Backend Error: ${res.status}`);
+        if (!res.ok) {
+            // Backend errors look like { error: { code, message } }
+            const body = await res.json().catch(() => null);
+            const error = new Error(body?.error?.message || `Backend Error: ${res.status}`);
+            error.code = body?.error?.code;
+            error.status = res.status;
+            throw error;
+        }
         return await res.json();
     } catch (error) {
         console.error(`Backend Fetch Error (${endpoint}):`, error);
@@ -16,19 +23,19 @@ export const getPlayerSummaries = async (apiKey, steamId) => {
     // Legacy support or direct use if needed, but preferable to use getBackendSteamData
     // For now, let's keep it but warn or redirect if we want full migration.
     // Given the task, let's stick to what's used in hooks.
-    return fetchBackend(`/steam/user/${steamId}`).then(data => data.player_summary);
+    return fetchBackend(`/steam/user/${encodeURIComponent(steamId)}`).then(data => data.player_summary);
 };
 
 export const getOwnedGames = async (apiKey, steamId) => {
-    return fetchBackend(`/steam/user/${steamId}`).then(data => data.owned_games);
+    return fetchBackend(`/steam/user/${encodeURIComponent(steamId)}`).then(data => data.owned_games);
 };
 
 export const getPlayerAchievements = async (apiKey, steamId, appid) => {
-    return fetchBackend(`/steam/user/${steamId}/achievements/${appid}`);
+    return fetchBackend(`/steam/user/${encodeURIComponent(steamId)}/achievements/${appid}`);
 };
 
 export const getBackendSteamData = async (steamId) => {
-    return fetchBackend(`/steam/user/${steamId}`);
+    return fetchBackend(`/steam/user/${encodeURIComponent(steamId)}`);
 };
 
 export const generateAIContent = async (prompt) => {